@@ -3,9 +3,12 @@ use rand::prelude::*;
 use log::info;
 use crate::game::{Square, Color, Piece, Move, BoardState};
 use crate::infoset::Infoset;
+use crate::session::Outcome;
+use crate::time_manager::{TimeManager, Phase};
 
 pub trait Ai {
-    fn make_player(&self, color: Color, seed: u64) -> Box<dyn Player>;
+    // increment: seconds added to the clock after each of our moves
+    fn make_player(&self, color: Color, seed: u64, increment: f64) -> Box<dyn Player>;
 }
 
 pub trait Player {
@@ -24,16 +27,24 @@ pub trait Player {
         requested: Option<Move>, taken: Option<Move>, capture: Option<(Square, Vec<Piece>)>,
         infoset: &Infoset,
         html: &mut dyn Write);
+    // Asked at the start of each of our turns, before sensing.
+    fn should_resign(&mut self, _infoset: &Infoset) -> bool {
+        false
+    }
+    // The last call, however the game ended. Without a winner and
+    // with reason "ERROR" if the game was lost track of.
+    fn game_over(&mut self, _outcome: &Outcome, _html: &mut dyn Write) {}
     fn get_summary(&self) -> String;
 }
 
 #[derive(Clone)]
 pub struct RandomAi {
+    // at most this many seconds of simulated thinking per decision
     pub delay: u64,
 }
 
 impl Ai for RandomAi {
-    fn make_player(&self, color: Color, seed: u64) -> Box<dyn Player> {
+    fn make_player(&self, color: Color, seed: u64, increment: f64) -> Box<dyn Player> {
         let mut state = BoardState::initial();
         state.fog_of_war(color);
         Box::new(RandomPlayer {
@@ -41,6 +52,11 @@ impl Ai for RandomAi {
             delay: self.delay,
             color,
             state,
+            time: TimeManager::new(increment),
+            ply: match color {
+                Color::White => 0,
+                Color::Black => 1,
+            },
         })
     }
 }
@@ -50,6 +66,18 @@ struct RandomPlayer {
     delay: u64,
     color: Color,
     state: BoardState,
+    time: TimeManager,
+    ply: usize,
+}
+
+impl RandomPlayer {
+    // Simulates thinking, within the budget.
+    fn think(&mut self, phase: Phase, remaining_time: f64, infoset: &Infoset) {
+        let deadline = self.time.start(phase, remaining_time, self.ply, infoset.possible_states.len());
+        let delay = (self.rng.gen_range(0, self.delay + 1) as f64).min(deadline.soft);
+        std::thread::sleep(std::time::Duration::from_secs_f64(delay));
+        self.time.finish(&deadline);
+    }
 }
 
 impl Player for RandomPlayer {
@@ -61,15 +89,12 @@ impl Player for RandomPlayer {
         _html: &mut dyn Write,
     ) {
         assert!(self.color != self.state.side_to_play());
-        std::thread::sleep(std::time::Duration::from_secs(
-            self.rng.gen_range(0, self.delay + 1)));
         self.state.make_move_under_fog(capture.map(|c| c.0));
     }
 
-    fn choose_sense(&mut self, _remaining_time: f64, _infoset: &Infoset, _html: &mut dyn Write) -> Vec<(Square, f32)> {
+    fn choose_sense(&mut self, remaining_time: f64, infoset: &Infoset, _html: &mut dyn Write) -> Vec<(Square, f32)> {
         assert_eq!(self.color, self.state.side_to_play());
-        std::thread::sleep(std::time::Duration::from_secs(
-            self.rng.gen_range(0, self.delay + 1)));
+        self.think(Phase::Sense, remaining_time, infoset);
         let mut result = Vec::new();
         for rank in 1..7 {
             for file in 1..7 {
@@ -85,15 +110,12 @@ impl Player for RandomPlayer {
         _html: &mut dyn Write,
     ) {
         assert_eq!(self.color, self.state.side_to_play());
-        std::thread::sleep(std::time::Duration::from_secs(
-            self.rng.gen_range(0, self.delay + 1)));
         info!("after sense: {:#?}", self.state.render());
     }
 
-    fn choose_move(&mut self, _remaining_time: f64, _infoset: &Infoset, _html: &mut dyn Write) -> Vec<(Option<Move>, f32)> {
+    fn choose_move(&mut self, remaining_time: f64, infoset: &Infoset, _html: &mut dyn Write) -> Vec<(Option<Move>, f32)> {
         assert_eq!(self.color, self.state.side_to_play());
-        std::thread::sleep(std::time::Duration::from_secs(
-            self.rng.gen_range(0, self.delay + 1)));
+        self.think(Phase::Move, remaining_time, infoset);
         // TODO: with some probability, try arbitrary random moves,
         // not only sensible ones
         self.state.all_sensible_requested_moves()
@@ -111,12 +133,11 @@ impl Player for RandomPlayer {
         assert_eq!(self.color, self.state.side_to_play());
         self.state.make_move(taken);
         self.state.fog_of_war(self.color);
+        self.ply += 2;
         info!("after move: {:#?}", self.state.render());
-        std::thread::sleep(std::time::Duration::from_secs(
-            self.rng.gen_range(0, self.delay + 1)));
     }
 
     fn get_summary(&self) -> String {
-        String::new()
+        self.time.summary()
     }
 }
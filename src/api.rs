@@ -1,80 +1,266 @@
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io::Write;
 use log::{info, error};
 use serde::{Serialize, Deserialize};
 use serde::de::DeserializeOwned;
 
-use crate::game::{Square, Color, Piece};
+use crate::game::{self, Square, Color, Piece};
+use crate::history::GameHistory;
+use crate::session::{Transport, TransportError, TransportResult, Status, MoveResult, Outcome, SenseResult};
 
 const SERVER_URL: &str = "https://rbc.jhuapl.edu";
 
+// Added to the clock after each move in games on the server.
+pub const SECONDS_INCREMENT: f64 = 5.0;
+
 // let auth = base64::encode(&format!("{}:{}", "genetic", "***REMOVED***"));
 // let auth = format!("Basic {}", auth);
 const AUTH: &str = "Basic ***REMOVED***";
 
+// Bodies of the server's 400 responses that have their own Error variant.
+// The wording is a guess, not seen in recorded traffic, so these are only
+// hints: HttpTransport asks game_status() whether the game is over on any
+// 400, and anything else is Error::BadRequest.
+const MSG_GAME_OVER: &str = "Game is over.";
+const MSG_NOT_YOUR_TURN: &str = "It is not your turn.";
+const MSG_INVALID_MOVE: &str = "Invalid move.";
+
 #[derive(Debug)]
 pub enum Error {
-    HttpError(i32),
-    Other(Box<dyn std::error::Error>),
+    GameOver { body: String },
+    NotYourTurn { body: String },
+    InvalidMove { body: String },
+    Auth { status: i32, body: String },
+    RateLimited { body: String, retry_after: Option<f64> },
+    NotFound { body: String },
+    // Other 4xx the server didn't explain in a recognizable way.
+    BadRequest { status: i32, body: String },
+    Server { status: i32, body: String },
+    Network(std::io::Error),
+    MalformedJson { body: String, error: serde_json::Error },
+    // Request that doesn't match the recording, see Wire::Replay.
+    Replay(String),
+}
+
+impl Error {
+    fn from_response(status: i32, body: String, retry_after: Option<f64>) -> Error {
+        match status {
+            401 | 403 => Error::Auth { status, body },
+            404 => Error::NotFound { body },
+            429 => Error::RateLimited { body, retry_after },
+            // The server only explains itself in the message.
+            400 => match body.trim() {
+                MSG_GAME_OVER => Error::GameOver { body },
+                MSG_NOT_YOUR_TURN => Error::NotYourTurn { body },
+                MSG_INVALID_MOVE => Error::InvalidMove { body },
+                _ => Error::BadRequest { status, body },
+            },
+            _ if status < 500 => Error::BadRequest { status, body },
+            _ => Error::Server { status, body },
+        }
+    }
+
+    // Worth trying the same request again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::RateLimited { .. } | Error::Server { .. } | Error::Network(_))
+    }
+
+    pub fn status(&self) -> Option<i32> {
+        match *self {
+            Error::GameOver { .. } | Error::NotYourTurn { .. } | Error::InvalidMove { .. } => Some(400),
+            Error::NotFound { .. } => Some(404),
+            Error::RateLimited { .. } => Some(429),
+            Error::Auth { status, .. } | Error::BadRequest { status, .. } | Error::Server { status, .. } => Some(status),
+            Error::Network(_) | Error::MalformedJson { .. } | Error::Replay(_) => None,
+        }
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Error::GameOver { body } | Error::NotYourTurn { body } | Error::InvalidMove { body } |
+            Error::Auth { body, .. } | Error::RateLimited { body, .. } | Error::NotFound { body } |
+            Error::BadRequest { body, .. } | Error::Server { body, .. } |
+            Error::MalformedJson { body, .. } => Some(body),
+            Error::Network(_) | Error::Replay(_) => None,
+        }
+    }
 }
 
-impl<T: Into<Box<dyn std::error::Error>>> From<T> for Error {
-    fn from(e: T) -> Error {
-        Error::Other(e.into())
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Network(e) => write!(f, "network error: {}", e),
+            Error::MalformedJson { body, error } => write!(f, "malformed json ({}): {}", error, body.trim_end()),
+            Error::Replay(msg) => write!(f, "replay: {}", msg),
+            e => write!(f, "{:?}", e),
+        }
     }
 }
 
+impl std::error::Error for Error {}
+
 type MyResult<T> = Result<T, Error>;
 
-fn retry_request(make_req: impl Fn() -> minreq::Request) -> MyResult<minreq::Response> {
-    let mut attempts = 5;
-    loop {
-        let e = match make_req().send() {
+// One HTTP request and what came back.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub addr: String,
+    pub request: Option<String>,
+    // 0 if the request didn't get through, then the body is the error
+    pub status: i32,
+    pub body: String,
+    pub retry_after: Option<f64>,
+}
+
+// Where the requests of the current thread go.
+pub enum Wire {
+    Live,
+    // to the server, and each exchange is appended to the file as a line of JSON
+    Record(std::fs::File),
+    // nowhere, answers come from a recording, in order
+    Replay(VecDeque<Exchange>),
+}
+
+thread_local!(static WIRE: RefCell<Wire> = const { RefCell::new(Wire::Live) });
+
+// Returns the previous one.
+pub fn set_wire(wire: Wire) -> Wire {
+    WIRE.with(|w| std::mem::replace(&mut *w.borrow_mut(), wire))
+}
+
+// How many times requests from the current thread are tried, see retry_request().
+// Once while an HttpTransport is alive: GameSession does the backing off then.
+thread_local!(static ATTEMPTS: Cell<u32> = const { Cell::new(5) });
+
+// Returns the previous number.
+pub fn set_attempts(attempts: u32) -> u32 {
+    assert!(attempts >= 1);
+    ATTEMPTS.with(|a| a.replace(attempts))
+}
+
+fn is_replaying() -> bool {
+    WIRE.with(|w| matches!(*w.borrow(), Wire::Replay(_)))
+}
+
+// As saved by Wire::Record.
+pub fn load_exchanges(path: impl AsRef<std::path::Path>) -> std::io::Result<VecDeque<Exchange>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+        .collect()
+}
+
+// POST if there is a request body, GET otherwise.
+fn exchange(addr: &str, request: Option<&str>) -> MyResult<Exchange> {
+    let method = if request.is_some() { "POST" } else { "GET" };
+    WIRE.with(|w| {
+        let mut w = w.borrow_mut();
+        if let Wire::Replay(exchanges) = &mut *w {
+            let ex = exchanges.front().ok_or_else(||
+                Error::Replay(format!("nothing recorded for {} {}", method, addr)))?;
+            if ex.method != method || ex.addr != addr || ex.request.as_deref() != request {
+                return Err(Error::Replay(format!(
+                    "{} {} {:?} instead of recorded {} {} {:?}",
+                    method, addr, request, ex.method, ex.addr, ex.request)));
+            }
+            return Ok(exchanges.pop_front().unwrap());
+        }
+
+        let url = format!("{}{}", SERVER_URL, addr);
+        let req = match request {
+            Some(payload) => minreq::post(url).with_body(payload),
+            None => minreq::get(url),
+        };
+        let resp = req
+            .with_header("Authorization", AUTH)
+            .with_timeout(10)
+            .send();
+        let (status, body, retry_after) = match resp {
             Ok(resp) => {
-                info!("got {} {}", resp.status_code, resp.body.trim_end());
-                match resp.status_code {
-                    200 => return Ok(resp),
-                    400..=499 => return Err(Error::HttpError(resp.status_code)),
-                    _ => Error::HttpError(resp.status_code)
-                }
+                let retry_after = resp.headers.iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case("retry-after"))
+                    .and_then(|(_, v)| v.trim().parse().ok());
+                (resp.status_code, resp.body, retry_after)
             }
-            Err(e) => e.into()
+            Err(e) => (0, e.to_string(), None),
+        };
+        let ex = Exchange {
+            method: method.to_owned(),
+            addr: addr.to_owned(),
+            request: request.map(str::to_owned),
+            status,
+            body,
+            retry_after,
+        };
+        if let Wire::Record(f) = &mut *w {
+            if let Err(e) = writeln!(f, "{}", serde_json::to_string(&ex).unwrap()) {
+                error!("recording: {}", e);
+            }
+        }
+        Ok(ex)
+    })
+}
+
+// Returns the body of the successful response.
+fn retry_request(addr: &str, request: Option<&str>) -> MyResult<String> {
+    let mut attempts = ATTEMPTS.with(Cell::get);
+    loop {
+        let ex = exchange(addr, request)?;
+        let e = if ex.status == 0 {
+            Error::Network(std::io::Error::other(ex.body))
+        } else {
+            info!("got {} {}", ex.status, ex.body.trim_end());
+            if ex.status == 200 {
+                return Ok(ex.body);
+            }
+            Error::from_response(ex.status, ex.body, ex.retry_after)
         };
         attempts -= 1;
-        if attempts == 0 {
+        if attempts == 0 || !e.is_retryable() {
             return Err(e);
         }
-        error!("{:?}", e);
-        info!("retrying...");
-        std::thread::sleep(std::time::Duration::from_secs(5));
+        error!("{}", e);
+        let delay = match e {
+            Error::RateLimited { retry_after: Some(t), .. } => t.max(5.0),
+            _ => 5.0,
+        };
+        if is_replaying() {
+            continue;
+        }
+        info!("retrying in {}s...", delay);
+        std::thread::sleep(std::time::Duration::from_secs_f64(delay));
     }
 }
 
+fn parse_json<Response: DeserializeOwned>(body: String) -> MyResult<Response> {
+    serde_json::from_str(&body).map_err(|error| Error::MalformedJson { body, error })
+}
+
 fn make_get_request_raw(addr: &str) -> MyResult<String> {
     info!("GET {}", addr);
-    let resp = retry_request(||
-        minreq::get(format!("{}{}", SERVER_URL, addr))
-        .with_header("Authorization", AUTH)
-        .with_timeout(10)
-    )?;
-    Ok(resp.body)
+    retry_request(addr, None)
 }
 
 fn make_get_request<Response: DeserializeOwned>(addr: &str) -> MyResult<Response> {
-    Ok(serde_json::from_str(&make_get_request_raw(addr)?)?)
+    parse_json(make_get_request_raw(addr)?)
+}
+
+fn make_post_request_raw<Request: Serialize>(addr: &str, req: &Request) -> MyResult<String> {
+    let payload = serde_json::to_string(req).unwrap();
+    info!("POST {}, req: {}", addr, payload);
+    retry_request(addr, Some(&payload))
 }
 
 fn make_post_request<Request: Serialize, Response: DeserializeOwned>(
     addr: &str, req: &Request) -> MyResult<Response>
 {
-    let payload = serde_json::to_string(req).unwrap();
-    info!("POST {}, req: {}", addr, payload);
-    let resp = retry_request(||
-        minreq::post(format!("{}{}", SERVER_URL, addr))
-        .with_header("Authorization", AUTH)
-        .with_body(&payload)
-        .with_timeout(10)
-    )?;
-    Ok(serde_json::from_str(&resp.body)?)
+    parse_json(make_post_request_raw(addr, req)?)
 }
 
 #[derive(Debug)]
@@ -111,12 +297,18 @@ pub fn announce_myself() -> MyResult<UsersMeResponse> {
     make_post_request::<_, UsersMeResponse>("/api/users/me", &())
 }
 
-#[allow(dead_code)]  // TODO
 #[derive(Serialize)]
 struct UsersMeMaxGamesRequest {
     max_games: i32,
 }
 
+// How many games the server lets us play at once, including the ones
+// we started. The response is not interesting.
+pub fn set_max_games(max_games: i32) -> MyResult<()> {
+    make_post_request_raw("/api/users/me/max_games", &UsersMeMaxGamesRequest { max_games })?;
+    Ok(())
+}
+
 #[derive(Debug)]
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -201,6 +393,18 @@ pub fn game_color(game_id: i32) -> MyResult<Color> {
     .map(|r| r.color)
 }
 
+#[derive(Debug)]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OpponentNameResponse {
+    opponent_name: String,
+}
+
+pub fn opponent_name(game_id: i32) -> MyResult<String> {
+    Ok(make_get_request::<OpponentNameResponse>(&format!("/api/games/{}/opponent_name", game_id))?
+       .opponent_name)
+}
+
 #[derive(Debug)]
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -378,7 +582,331 @@ pub struct GameHistoryResponse {
     pub game_history: RawGameHistory,
 }
 
+pub fn resign(game_id: i32) -> MyResult<()> {
+    make_post_request_raw(&format!("/api/games/{}/resign", game_id), &())?;
+    Ok(())
+}
+
 pub fn game_history_raw(game_id: i32) -> MyResult<String> {
     let addr = format!("/api/games/{}/game_history", game_id);
     make_get_request_raw(&addr)
 }
+
+// Turn protocol of one game over this API, see session::GameSession.
+// While it's alive, requests from this thread are tried once,
+// GameSession retries transient errors.
+pub struct HttpTransport {
+    pub game_id: i32,
+    // what set_wire() and set_attempts() replaced, restored on drop
+    prev_wire: Option<Wire>,
+    prev_attempts: u32,
+}
+
+impl HttpTransport {
+    pub fn new(game_id: i32) -> HttpTransport {
+        HttpTransport { game_id, prev_wire: None, prev_attempts: set_attempts(1) }
+    }
+
+    // While it's alive, all requests from this thread are also saved to the file.
+    pub fn recording(game_id: i32, path: impl AsRef<std::path::Path>) -> std::io::Result<HttpTransport> {
+        let wire = Wire::Record(std::fs::File::create(path)?);
+        let mut t = HttpTransport::new(game_id);
+        t.prev_wire = Some(set_wire(wire));
+        Ok(t)
+    }
+
+    // While it's alive, requests from this thread are answered from
+    // such a recording, and have to be exactly the recorded ones.
+    pub fn replaying(game_id: i32, path: impl AsRef<std::path::Path>) -> std::io::Result<HttpTransport> {
+        let wire = Wire::Replay(load_exchanges(path)?);
+        let mut t = HttpTransport::new(game_id);
+        t.prev_wire = Some(set_wire(wire));
+        Ok(t)
+    }
+
+    fn convert<T>(&self, r: MyResult<T>) -> TransportResult<T> {
+        r.map_err(|e| {
+            error!("{}", e);
+            match e {
+                // Once the game is over (for example on timeout) the server
+                // rejects everything, and the message can't be relied on.
+                Error::GameOver { .. } | Error::NotYourTurn { .. } | Error::InvalidMove { .. } |
+                Error::BadRequest { status: 400, .. } => match game_status(self.game_id) {
+                    Ok(gs) if gs.is_over => TransportError::GameOver,
+                    Ok(_) => match e {
+                        Error::InvalidMove { body } => TransportError::InvalidMove(body),
+                        e => TransportError::Fatal(e.to_string()),
+                    },
+                    Err(e2) => TransportError::Fatal(format!("{}; then {}", e, e2)),
+                },
+                e if e.is_retryable() => TransportError::Transient(e.to_string()),
+                e => TransportError::Fatal(e.to_string()),
+            }
+        })
+    }
+}
+
+impl Drop for HttpTransport {
+    fn drop(&mut self) {
+        if let Some(wire) = self.prev_wire.take() {
+            set_wire(wire);
+        }
+        set_attempts(self.prev_attempts);
+    }
+}
+
+impl Transport for HttpTransport {
+    fn status(&mut self) -> TransportResult<Status> {
+        let gs = self.convert(game_status(self.game_id))?;
+        Ok(Status { is_my_turn: gs.is_my_turn, is_over: gs.is_over })
+    }
+
+    fn seconds_left(&mut self) -> TransportResult<f64> {
+        self.convert(seconds_left(self.game_id)).map(f64::from)
+    }
+
+    fn opponent_move_results(&mut self) -> TransportResult<Option<Square>> {
+        self.convert(opponent_move_results(self.game_id))
+    }
+
+    fn sense(&mut self, square: Square) -> TransportResult<SenseResult> {
+        self.convert(sense(self.game_id, square))
+    }
+
+    fn make_move(&mut self, requested: Option<game::Move>) -> TransportResult<MoveResult> {
+        let req_str = requested.map_or("a1a1".to_owned(), |r| r.to_uci());
+        let mr = self.convert(make_move(self.game_id, req_str))?;
+        Ok(MoveResult {
+            requested: mr.requested.map(|m| game::Move::from_uci(&m)),
+            taken: mr.taken.map(|m| game::Move::from_uci(&m)),
+            capture_square: mr.capture_square,
+        })
+    }
+
+    fn end_turn(&mut self) -> TransportResult<()> {
+        self.convert(end_turn(self.game_id))
+    }
+
+    fn resign(&mut self) -> TransportResult<()> {
+        self.convert(resign(self.game_id))
+    }
+
+    fn outcome(&mut self) -> TransportResult<Outcome> {
+        let h = self.convert(game_history_raw(self.game_id))?;
+        let h: GameHistoryResponse = self.convert(parse_json(h))?;
+        let winner = h.game_history.winner_color;
+        let reason = h.game_history.win_reason.0.clone();
+        // the result counts even if the details don't add up
+        let history = GameHistory::try_from(h.game_history)
+            .map_err(|e| error!("game history: {}", e))
+            .ok();
+        Ok(Outcome { winner, reason, history })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ex(addr: &str, request: Option<&str>, status: i32, body: &str) -> Exchange {
+        Exchange {
+            method: if request.is_some() { "POST" } else { "GET" }.to_owned(),
+            addr: addr.to_owned(),
+            request: request.map(str::to_owned),
+            status,
+            body: body.to_owned(),
+            retry_after: None,
+        }
+    }
+
+    #[test]
+    fn test_game_over_message() {
+        let e = Error::from_response(400, MSG_GAME_OVER.to_owned() + "\n", None);
+        assert!(matches!(e, Error::GameOver { .. }), "{:?}", e);
+        assert_eq!(e.status(), Some(400));
+        assert_eq!(e.body(), Some("Game is over.\n"));
+    }
+
+    #[test]
+    fn test_not_your_turn_message() {
+        let e = Error::from_response(400, MSG_NOT_YOUR_TURN.to_owned(), None);
+        assert!(matches!(e, Error::NotYourTurn { .. }), "{:?}", e);
+        assert!(!e.is_retryable());
+    }
+
+    #[test]
+    fn test_invalid_move_message() {
+        let e = Error::from_response(400, MSG_INVALID_MOVE.to_owned(), None);
+        assert!(matches!(e, Error::InvalidMove { .. }), "{:?}", e);
+    }
+
+    #[test]
+    fn test_other_messages() {
+        // No guessing from the words.
+        for body in &["The game is not over.", "game over", "Invalid move. Also, game is over.", ""] {
+            let e = Error::from_response(400, (*body).to_owned(), None);
+            assert!(matches!(e, Error::BadRequest { status: 400, .. }), "{:?}", e);
+        }
+        assert!(matches!(Error::from_response(403, MSG_GAME_OVER.to_owned(), None), Error::Auth { .. }));
+        assert!(Error::from_response(502, String::new(), None).is_retryable());
+    }
+
+    #[test]
+    fn test_inconsistent_history() {
+        // a sense result missing, and no fens at all for black
+        let history = r#"{"game_history": {"type": "GameHistory",
+            "white_name": "alice", "black_name": "bob",
+            "winner_color": false, "win_reason": {"type": "WinReason", "value": "TIMEOUT"},
+            "senses": {"true": [12, 12], "false": []},
+            "sense_results": {"true": [], "false": []},
+            "requested_moves": {"true": [null], "false": []}, "taken_moves": {"true": [null], "false": []},
+            "capture_squares": {"true": [null], "false": []},
+            "fens_before_move": {"true": [""]}, "fens_after_move": {"true": [""]}}}"#;
+        let prev = set_wire(Wire::Replay(vec![
+            ex("/api/games/6/game_history", None, 200, history),
+        ].into()));
+        let outcome = HttpTransport::new(6).outcome().unwrap();
+        assert_eq!(outcome.winner, Some(Color::Black));
+        assert_eq!(outcome.reason, "TIMEOUT");
+        assert!(outcome.history.is_none());
+        set_wire(prev);
+    }
+
+    // The game_status() says, not the message.
+    #[test]
+    fn test_400_checks_game_status() {
+        let prev = set_wire(Wire::Replay(vec![
+            ex("/api/games/5/end_turn", Some("null"), 400, "Bad Request"),
+            ex("/api/games/5/game_status", None, 200, r#"{"is_my_turn": false, "is_over": true}"#),
+            ex("/api/games/5/end_turn", Some("null"), 400, MSG_GAME_OVER),
+            ex("/api/games/5/game_status", None, 200, r#"{"is_my_turn": true, "is_over": false}"#),
+            ex("/api/games/5/move", Some(r#"{"requested_move":{"type":"Move","value":"e2e5"}}"#), 400, MSG_INVALID_MOVE),
+            ex("/api/games/5/game_status", None, 200, r#"{"is_my_turn": true, "is_over": false}"#),
+        ].into()));
+        let mut t = HttpTransport::new(5);
+        assert!(matches!(t.end_turn(), Err(TransportError::GameOver)));
+        assert!(matches!(t.end_turn(), Err(TransportError::Fatal(_))));
+        assert!(matches!(t.make_move(Some(game::Move::from_uci("e2e5"))), Err(TransportError::InvalidMove(_))));
+        set_wire(prev);
+    }
+
+    #[test]
+    fn test_replay() {
+        let history = r#"{"game_history": {"type": "GameHistory",
+            "white_name": "alice", "black_name": "bob",
+            "winner_color": true, "win_reason": {"type": "WinReason", "value": "KING_CAPTURE"},
+            "senses": {"true": [12], "false": []},
+            "sense_results": {"true": [[]], "false": []},
+            "requested_moves": {"true": [], "false": []}, "taken_moves": {"true": [], "false": []},
+            "capture_squares": {"true": [], "false": []},
+            "fens_before_move": {"true": [], "false": []}, "fens_after_move": {"true": [], "false": []}}}"#;
+        let recording = vec![
+            ex("/api/games/7/game_status", None, 503, "busy"),
+            ex("/api/games/7/game_status", None, 200, r#"{"is_my_turn": true, "is_over": false}"#),
+            ex("/api/games/7/seconds_left", None, 200, r#"{"seconds_left": 899.5}"#),
+            ex("/api/games/7/opponent_move_results", None, 200, r#"{"opponent_move_results": 12}"#),
+            ex("/api/games/7/sense", Some(r#"{"square":20}"#), 200,
+               r#"{"sense_result": [[20, {"type": "Piece", "value": "p"}], [21, null]]}"#),
+            ex("/api/games/7/move", Some(r#"{"requested_move":{"type":"Move","value":"e7e5"}}"#), 200,
+               r#"{"move_result": [{"type": "Move", "value": "e7e5"}, {"type": "Move", "value": "e7e5"}, null]}"#),
+            ex("/api/games/7/end_turn", Some("null"), 200, "{}"),
+            ex("/api/games/7/game_history", None, 200, history),
+        ];
+        let path = std::env::temp_dir().join(format!("rbc_test_replay_{}.jsonl", std::process::id()));
+        let lines: Vec<String> = recording.iter().map(|e| serde_json::to_string(e).unwrap()).collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        assert_eq!(load_exchanges(&path).unwrap(), recording);
+
+        let mut t = HttpTransport::replaying(7, &path).unwrap();
+        // tried once, GameSession retries
+        assert!(matches!(t.status(), Err(TransportError::Transient(_))));
+        let status = t.status().unwrap();
+        assert!(status.is_my_turn && !status.is_over);
+        assert_eq!(t.seconds_left().unwrap(), 899.5);
+        assert_eq!(t.opponent_move_results().unwrap(), Some(Square(12)));
+        assert_eq!(t.sense(Square(20)).unwrap(), vec![(Square(20), Some(Piece::from_char('p'))), (Square(21), None)]);
+        let mr = t.make_move(Some(game::Move::from_uci("e7e5"))).unwrap();
+        assert_eq!(mr.taken, Some(game::Move::from_uci("e7e5")));
+        assert_eq!(mr.capture_square, None);
+        t.end_turn().unwrap();
+        let outcome = t.outcome().unwrap();
+        assert_eq!(outcome.winner, Some(Color::White));
+        assert_eq!(outcome.reason, "KING_CAPTURE");
+        let history = outcome.history.unwrap();
+        assert_eq!((history.white_name.as_str(), history.black_name.as_str()), ("alice", "bob"));
+        assert!(history.moves.is_empty());
+        // past the end of the recording
+        assert!(matches!(t.end_turn(), Err(TransportError::Fatal(_))));
+        drop(t);
+        assert!(!is_replaying());
+
+        // requests have to match
+        let mut t = HttpTransport::replaying(7, &path).unwrap();
+        match seconds_left(7) {
+            Err(Error::Replay(msg)) => assert!(msg.contains("game_status"), "{}", msg),
+            r => panic!("{:?}", r),
+        }
+        // the mismatched one isn't consumed
+        assert!(t.status().is_err());
+        assert!(t.status().is_ok());
+        assert_eq!(t.seconds_left().unwrap(), 899.5);
+
+        // outside of games retries aren't waited for
+        drop(t);
+        let mut t = HttpTransport::replaying(7, &path).unwrap();
+        let prev = set_attempts(5);
+        let start = std::time::Instant::now();
+        assert!(!game_status(7).unwrap().is_over);
+        assert!(start.elapsed().as_secs() < 1);
+        set_attempts(prev);
+        assert_eq!(t.seconds_left().unwrap(), 899.5);
+
+        // nested, the outer one is back after the inner one is gone
+        let inner = HttpTransport::replaying(8, &path).unwrap();
+        drop(inner);
+        assert!(is_replaying());
+        assert!(t.seconds_left().is_err(), "opponent_move_results is next");
+        drop(t);
+        assert!(!is_replaying());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // A whole game, but written by hand in the format Wire::Record uses,
+    // response bodies included: there is no recording of a server game yet.
+    // A trimmed logs/game_NNNNN.http.jsonl from a real game should replace it.
+    #[test]
+    fn test_replay_game() {
+        use crate::session::{GameSession, host_player};
+        use crate::session::tests::ScriptedPlayer;
+        use rand::prelude::*;
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/handwritten_game.http.jsonl");
+        for line in std::fs::read_to_string(path).unwrap().lines() {
+            let ex: Exchange = serde_json::from_str(line).unwrap();
+            assert_eq!(serde_json::to_string(&ex).unwrap(), line, "not as recorded");
+        }
+        let t = HttpTransport::replaying(42, path).unwrap();
+        let mut session = GameSession::new(t, Color::White);
+        session.poll_interval = std::time::Duration::from_millis(1);
+        session.max_backoff = std::time::Duration::from_millis(1);
+        let mut player = ScriptedPlayer::new("e7", &["e2e4", "d1h5", "h5e8"]);
+        let mut rng = StdRng::seed_from_u64(42);
+        let outcome = host_player(&mut session, &mut player, &mut rng, &mut std::io::sink()).unwrap();
+        assert_eq!(outcome.winner, Some(Color::White));
+        assert_eq!(outcome.reason, "KING_CAPTURE");
+        assert_eq!(player.reason.as_deref(), Some("KING_CAPTURE"));
+        assert_eq!(session.ply(), 6);
+        assert!(session.seconds_left() > 886.0 && session.seconds_left() <= 886.25);
+        let history = outcome.history.unwrap();
+        assert_eq!((history.white_name.as_str(), history.black_name.as_str()), ("genetic", "random"));
+        assert_eq!(history.moves.len(), 5);
+        assert_eq!(history.moves[4].capture_square, Some(Square::from_san("e8")));
+        // all of it was used
+        match game_status(42) {
+            Err(Error::Replay(msg)) => assert!(msg.contains("nothing recorded"), "{}", msg),
+            r => panic!("{:?}", r),
+        }
+        drop(session);
+        assert!(!is_replaying());
+    }
+}
@@ -1,4 +1,4 @@
-use rbc::cfr::{NodeInfo, Game, Encoding, Cfr};
+use rbc::cfr::{NodeInfo, Game, Encoding, Cfr, Solver};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Coin {
@@ -26,9 +26,18 @@ struct CoinTossGame;
 impl Game for CoinTossGame {
     type Action = CoinTossAction;
     type Infoset = CoinTossInfoset;
+    type State = Vec<CoinTossAction>;
 
-    fn node_info(&mut self, h: &[Self::Action]) -> NodeInfo<Self::Action, Self::Infoset> {
-        match h {
+    fn initial_state(&mut self) -> Self::State {
+        Vec::new()
+    }
+
+    fn apply(&mut self, h: &mut Self::State, a: &Self::Action) {
+        h.push(a.clone());
+    }
+
+    fn node_info(&mut self, h: &Self::State) -> NodeInfo<Self::Action, Self::Infoset> {
+        match &h[..] {
             [] => NodeInfo::Chance(vec![
                 (0.5, CoinTossAction::Toss(Coin::Heads)),
                 (0.5, CoinTossAction::Toss(Coin::Tails)),
@@ -90,9 +99,18 @@ struct RpsGame;
 impl Game for RpsGame {
     type Action = Rps;
     type Infoset = u8;
+    type State = Vec<Rps>;
+
+    fn initial_state(&mut self) -> Self::State {
+        Vec::new()
+    }
+
+    fn apply(&mut self, h: &mut Self::State, a: &Self::Action) {
+        h.push(a.clone());
+    }
 
-    fn node_info(&mut self, h: &[Self::Action]) -> NodeInfo<Self::Action, Self::Infoset> {
-        match h {
+    fn node_info(&mut self, h: &Self::State) -> NodeInfo<Self::Action, Self::Infoset> {
+        match &h[..] {
             [] => NodeInfo::Choice {
                 player: 0,
                 infoset: 0,
@@ -128,4 +146,116 @@ fn main() {
     }
     dbg!(&cfr);
     dbg!(cfr.get_strategy(&enc));
+    dbg!(rbc::cfr::nash_conv(&enc, &cfr.average_strategy(&enc)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rbc::cfr::{nash_conv, Variant};
+    use rbc::mccfr::{Mccfr, Sampling};
+
+    // Nash conv after 10000 iterations should be below the given bound.
+    const VARIANTS: [(Variant, f32); 4] = [
+        (Variant::Vanilla, 0.05),
+        (Variant::CfrPlus, 1e-3),
+        (Variant::Linear, 1e-3),
+        (Variant::DCFR, 1e-3),
+    ];
+
+    fn solve<G: Game>(enc: &Encoding<G>, variant: Variant, steps: usize) -> f32 {
+        let mut cfr = Cfr::with_variant(enc, variant);
+        for _ in 0..steps {
+            cfr.step(enc);
+        }
+        nash_conv(enc, &cfr.average_strategy(enc))
+    }
+
+    #[test]
+    fn test_rps_uniform() {
+        let enc = Encoding::new(&mut RpsGame);
+        let uniform = vec![vec![1.0 / 3.0; 3]; 2];
+        // scissors wins 1/3 on average, the second player can't gain anything
+        assert!((rbc::cfr::best_response_value(&enc, &uniform, 0) - 1.0 / 3.0).abs() < 1e-6);
+        assert!(rbc::cfr::best_response_value(&enc, &uniform, 1).abs() < 1e-6);
+        assert!((nash_conv(&enc, &uniform) - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rps_converges() {
+        let enc = Encoding::new(&mut RpsGame);
+        for &(variant, bound) in &VARIANTS {
+            let nc = solve(&enc, variant, 10_000);
+            assert!(nc >= -1e-6 && nc < bound, "{:?} {}", variant, nc);
+        }
+    }
+
+    #[test]
+    fn test_coin_toss_converges() {
+        let enc = Encoding::new(&mut CoinTossGame);
+        for &(variant, bound) in &VARIANTS {
+            let nc = solve(&enc, variant, 10_000);
+            assert!(nc >= -1e-6 && nc < bound, "{:?} {}", variant, nc);
+        }
+        assert!(solve(&enc, Variant::Vanilla, 0) > 0.1);
+    }
+
+    #[cfg(feature = "heavy")]
+    #[test]
+    fn test_parallel_cfr() {
+        use rbc::cfr::ParallelCfr;
+        let enc = Encoding::new(&mut CoinTossGame);
+        let solve_par = |variant, alternating, split_depth| {
+            let mut cfr = ParallelCfr::with_variant(&enc, variant);
+            cfr.cfr.alternating = alternating;
+            cfr.split_depth = split_depth;
+            for _ in 0..10_000 {
+                cfr.step(&enc);
+            }
+            cfr.average_strategy(&enc)
+        };
+        for &(variant, bound) in &VARIANTS {
+            for alternating in [false, true] {
+                let mut cfr = Cfr::with_variant(&enc, variant);
+                cfr.alternating = alternating;
+                for _ in 0..10_000 {
+                    cfr.step(&enc);
+                }
+                let expected = cfr.average_strategy(&enc);
+                let bound = bound.max(2.0 * nash_conv(&enc, &expected));
+                for split_depth in [0, 1, 2, 10] {
+                    let sigma = solve_par(variant, alternating, split_depth);
+                    // merged in a fixed order, so bit for bit reproducible
+                    assert_eq!(sigma, solve_par(variant, alternating, split_depth));
+                    let nc = nash_conv(&enc, &sigma);
+                    assert!(nc >= -1e-6 && nc < bound, "{:?} {} {}", variant, split_depth, nc);
+                    // same up to rounding
+                    for (x, y) in sigma.iter().flatten().zip(expected.iter().flatten()) {
+                        assert!((x - y).abs() < 1e-2, "{:?} {} {} {}", variant, split_depth, x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    fn solve_mc<G: Game>(g: &mut G, enc: &Encoding<G>, sampling: Sampling, steps: usize) -> f32 {
+        let mut mccfr = Mccfr::new(sampling, 42);
+        for _ in 0..steps {
+            mccfr.step(g);
+        }
+        assert_eq!(mccfr.num_infosets(), enc.infosets.len());
+        nash_conv(enc, &mccfr.average_strategy(enc))
+    }
+
+    #[test]
+    fn test_mccfr_converges() {
+        let rps = Encoding::new(&mut RpsGame);
+        let coin_toss = Encoding::new(&mut CoinTossGame);
+        for &sampling in &[Sampling::External, Sampling::Outcome { exploration: 0.6 }] {
+            let nc = solve_mc(&mut RpsGame, &rps, sampling, 20_000);
+            assert!(nc < 0.05, "{:?} {}", sampling, nc);
+            let nc = solve_mc(&mut CoinTossGame, &coin_toss, sampling, 20_000);
+            assert!(nc < 0.05, "{:?} {}", sampling, nc);
+        }
+    }
 }
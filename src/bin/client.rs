@@ -1,161 +1,111 @@
 use std::io::Write;
+use std::sync::{Arc, Mutex};
 use log::{info, error};
 use rand::prelude::*;
 use rbc::logger::{ThreadLocalLogger, WriteLogger};
 use rbc::api;
-use rbc::game::{Color, Move};
+use rbc::game::Color;
 use rbc::ai_interface::Ai;
-use rbc::infoset::Infoset;
-use rbc::distr;
+use rbc::session::{self, GameSession};
+use rbc::notify::{Notifications, EventKind};
+use rbc::dashboard::Dashboard;
+use rbc::opponents::OpponentPolicy;
+use rbc::throttle::{Throttle, CpuMonitor};
+use rbc::shutdown::{Shutdown, Stage, AfterDeadline};
+
+const STATUS_PAGE: &str = "logs/status.html";
+const MY_NAME: &str = "genetic";
+
+// Used by both the main loop and game threads.
+pub struct Shared {
+    dashboard: Mutex<Dashboard>,
+    opponents: Mutex<OpponentPolicy>,
+    shutdown: Arc<Shutdown>,
+}
 
-pub fn play_game_no_panic(color: Color, game_id: i32, ai: &dyn Ai) -> (char, String) {
+pub fn play_game_no_panic(color: Color, game_id: i32, ai: &dyn Ai, shared: &Shared) -> (char, String) {
     let ai = std::panic::AssertUnwindSafe(ai);
     std::panic::catch_unwind(|| {
-        play_game(color, game_id, *ai)
+        play_game(color, game_id, *ai, shared)
     }).unwrap_or(('E', format!("{}: panic  ", game_id)))
 }
 
-pub fn play_game(color: Color, game_id: i32, ai: &dyn Ai) -> (char, String) {
+pub fn play_game(color: Color, game_id: i32, ai: &dyn Ai, shared: &Shared) -> (char, String) {
     let seed = rand::thread_rng().gen();
     info!("player seed: {}", seed);
-    let mut player = ai.make_player(color, seed);
+    let mut player = ai.make_player(color, seed, api::SECONDS_INCREMENT);
     let mut rng = StdRng::seed_from_u64(seed + 1);
 
-    let mut halfmove_number = match color {
-        Color::White => 0,
-        Color::Black => 1,
-    };
-
     let timer = std::time::Instant::now();
-    let mut last_time_left = 900.0;
 
     let html = std::fs::File::create(format!("logs/game_{:05}.html", game_id)).unwrap();
     let mut html = std::io::BufWriter::new(html);
     writeln!(html, "{}", rbc::html::PREAMBLE).unwrap();
 
-    let mut infoset = Infoset::new(color);
-
-    player.begin(&mut html);
-    loop {
-        let gs = api::game_status(game_id).expect("TODO");
-        if gs.is_over {
-            break;
+    // for api::HttpTransport::replaying()
+    let http_log = format!("logs/game_{:05}.http.jsonl", game_id);
+    let transport = api::HttpTransport::recording(game_id, &http_log).unwrap_or_else(|e| {
+        error!("{}: {}", http_log, e);
+        api::HttpTransport::new(game_id)
+    });
+    let mut session = GameSession::new(transport, color);
+    session.turn_policy = Box::new({
+        let shutdown = shared.shutdown.clone();
+        move || shutdown.turn_policy()
+    });
+    let mut observe = |s: &GameSession<api::HttpTransport>| {
+        if let Some(g) = shared.dashboard.lock().unwrap().games.get_mut(&game_id) {
+            g.ply = s.ply();
+            g.seconds_left = s.seconds_left();
+            g.possible_states = s.infoset().possible_states.len();
         }
-        if gs.is_my_turn {
-            writeln!(html, "<hr>").unwrap();
-            match api::seconds_left(game_id) {
-                Ok(t) => last_time_left = t as f64,
-                Err(api::Error::HttpError(400)) => {
-                    let gs = api::game_status(game_id).expect("TODO");
-                    assert!(gs.is_over);
-                    break;
-                }
-                Err(e) => panic!("{:?}", e),
-            }
-            let move_timer = std::time::Instant::now();
-            let capture_square = match api::opponent_move_results(game_id) {
-                Ok(cs) => cs,
-                Err(api::Error::HttpError(400)) => {
-                    let gs = api::game_status(game_id).expect("TODO");
-                    assert!(gs.is_over);
-                    break;
-                }
-                Err(e) => panic!("{:?}", e),
-            };
-
-            if halfmove_number > 0 {
-                let capture = infoset.opponent_move(capture_square);
-                player.handle_opponent_move(capture, &infoset, &mut html);
-            } else {
-                assert!(capture_square.is_none());
-            }
+    };
+    let result = session::host_player_observed(
+        &mut session, &mut *player, &mut rng, &mut html, &mut observe);
+    if let Err(e) = html.flush() {
+        error!("game_{:05}.html: {}", game_id, e);
+    }
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(msg) => return ('E', format!("{}: {}", game_id, msg)),
+    };
 
-            let sense_distr = player.choose_sense(last_time_left, &infoset, &mut html);
-            let sense = *distr::draw(&sense_distr, &mut rng);
-            writeln!(html, "<p>sense: {:?}</p>", sense_distr).unwrap();
-            writeln!(html, "<p>sense: {:?}</p>", sense).unwrap();
-            let sense_result = match api::sense(game_id, sense) {
-                Ok(sr) => sr,
-                Err(api::Error::HttpError(400)) => {
-                    let gs = api::game_status(game_id).expect("TODO");
-                    assert!(gs.is_over);
-                    break;
-                }
-                Err(e) => panic!("{:?}", e),
-            };
-            infoset.sense(sense, &sense_result);
-            player.handle_sense(sense, &sense_result, &infoset, &mut html);
-
-            let requested_distr = player.choose_move(
-                last_time_left - move_timer.elapsed().as_secs_f64(),
-                &infoset, &mut html);
-            let requested = distr::draw(&requested_distr, &mut rng);
-            writeln!(html, "<p>requested: {:?}</p>", requested_distr).unwrap();
-
-            let req_str = requested.map_or("a1a1".to_owned(), |r| r.to_uci());
-            let mr = match api::make_move(game_id, req_str) {
-                Ok(mr) => mr,
-                Err(api::Error::HttpError(400)) => {
-                    let gs = api::game_status(game_id).expect("TODO");
-                    assert!(gs.is_over);
-                    break;
-                }
-                Err(e) => panic!("{:?}", e),
-            };
-            let requested = mr.requested.map(|m| Move::from_uci(&m));
-            let taken = mr.taken.map(|m| Move::from_uci(&m));
-            let observed_capture = infoset.my_move(requested, taken, mr.capture_square);
-            player.handle_move(
-                requested,
-                taken,
-                observed_capture,
-                &infoset,
-                &mut html);
-
-            match api::end_turn(game_id) {
-                Ok(()) => {},
-                Err(api::Error::HttpError(400)) => {
-                    let gs = api::game_status(game_id).expect("TODO");
-                    assert!(gs.is_over);
-                    break;
-                }
-                Err(e) => panic!("{:?}", e),
-            }
-            halfmove_number += 2;
+    let opponent_name = outcome.history.as_ref().map(|h| match color {
+        Color::White => h.black_name.clone(),
+        Color::Black => h.white_name.clone(),
+    });
+    if let Some(name) = &opponent_name {
+        shared.opponents.lock().unwrap().record(name, color, outcome.winner, &outcome.reason);
+        if let Some(g) = shared.dashboard.lock().unwrap().games.get_mut(&game_id) {
+            g.opponent = Some(name.clone());
         }
-
-        std::thread::sleep(std::time::Duration::from_secs_f64(0.25));
     }
+    let opponent_name = opponent_name.unwrap_or_else(|| "?".to_owned());
 
-    let h = api::game_history_raw(game_id).expect("TODO");
-    let h: api::GameHistoryResponse = serde_json::from_str(&h).expect("TODO");
-    let h = h.game_history;
-    let opponent_name = match color {
-        Color::White => h.black_name,
-        Color::Black => h.white_name,
-    };
-
-    let outcome = match h.winner_color {
+    let outcome_char = match outcome.winner {
         None => 'D',
         Some(c) if color == c => 'W',
         _ => 'L',
     };
     let mut message = format!(
         "{}: {} {}; {} moves; {:.0}s/{:.0}s",
-        game_id, opponent_name, h.win_reason.0,
-        halfmove_number,
-        900.0 - last_time_left, timer.elapsed().as_secs_f64());
+        game_id, opponent_name, outcome.reason,
+        session.ply(),
+        900.0 - session.seconds_left(), timer.elapsed().as_secs_f64());
 
-    if outcome != 'W' {
+    if outcome_char != 'W' {
         message.push_str("   !!!");
     }
 
     info!("summary:\n{}", player.get_summary());
     info!("{}", message);
-    (outcome, message)
+    (outcome_char, message)
 }
 
 struct Slot {
+    game_id: i32,
+    // None if the server wouldn't tell
+    opponent: Option<String>,
     t: std::thread::JoinHandle<(char, String)>,
     is_challenger: bool,
 }
@@ -173,7 +123,7 @@ fn print_slots(slots: &[Option<Slot>], idx: usize, c: char) {
     }
 }
 
-fn panic_hook(pi: &std::panic::PanicInfo) {
+fn panic_hook(pi: &std::panic::PanicInfo, notifications: &Notifications) {
     let payload =
         if let Some(s) = pi.payload().downcast_ref::<&str>() {
             (*s).to_owned()
@@ -192,17 +142,35 @@ fn panic_hook(pi: &std::panic::PanicInfo) {
     error!("thread '{}' panicked at {:?}, {}\n{:?}", thread, payload, loc, bt);
     let message = format!("thread '{}' panicked at {:?}, {}", thread, payload, loc);
     println!("{}", message);
+    notifications.notify(EventKind::Panic, &message);
+}
 
-    if let Ok(teleg_bot) = std::env::var("TELEG_BOT") {
-        let resp = minreq::get(format!("{}{}", teleg_bot, message))
-            .with_timeout(10)
-            .send();
-        if let Err(e) = resp {
-            error!("{:?}", e);
+// For requests outside of games, which will simply be tried again
+// in the next round. Except bad credentials, that won't get better,
+// so no new games, as after Ctrl-C.
+fn report_error(notifications: &Notifications, shutdown: &Shutdown, e: &api::Error) {
+    error!("{}", e);
+    println!("{}", e);
+    notifications.notify(EventKind::ApiError, &e.to_string());
+    if let api::Error::Auth { .. } = e {
+        if shutdown.accepting_games() {
+            shutdown.request();
+            println!("check credentials, no new games");
         }
     }
 }
 
+fn num_games(slots: &[Option<Slot>]) -> usize {
+    slots.iter().filter(|s| s.is_some()).count()
+}
+
+// Comma separated names.
+fn env_list(var: &str) -> std::collections::HashSet<String> {
+    std::env::var(var).unwrap_or_default()
+        .split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_owned)
+        .collect()
+}
+
 fn main() {
     log::set_logger(&ThreadLocalLogger).unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -223,118 +191,306 @@ fn main() {
     let max_challenge_threads = arg.abs() as usize;
     let accept_invites = arg >= 0;
 
-    let ai = rbc::greedy::GreedyAi { experiment: true };
+    let ai = rbc::greedy::GreedyAi {
+        experiment: true,
+        cfr_variant: rbc::cfr::Variant::Vanilla,
+        cfr_target_nash_conv: None,
+        cfr_parallel: false,
+    };
 
-    use std::sync::atomic::{AtomicBool, Ordering};
-    let running = std::sync::Arc::new(AtomicBool::new(true));
+    let grace = match std::env::var("RBC_SHUTDOWN_GRACE") {
+        Ok(s) => s.parse().unwrap_or_else(|_| {
+            eprintln!("RBC_SHUTDOWN_GRACE: {:?}, expected seconds", s);
+            std::process::exit(1);
+        }),
+        Err(_) => 300,
+    };
+    let after_deadline = match std::env::var("RBC_SHUTDOWN_POLICY") {
+        Ok(s) => AfterDeadline::from_name(&s).unwrap_or_else(|| {
+            eprintln!("RBC_SHUTDOWN_POLICY: {:?}, expected fast or resign", s);
+            std::process::exit(1);
+        }),
+        Err(_) => AfterDeadline::PlayFast,
+    };
+    let shutdown = Arc::new(Shutdown::new(std::time::Duration::from_secs(grace), after_deadline));
+    println!("on Ctrl-C: {}s for the games in progress, then {:?}", grace, after_deadline);
     ctrlc::set_handler({
-        let running = running.clone();
+        let shutdown = shutdown.clone();
         move || {
             // logging won't work here because it's a separate thread
-            if !running.load(Ordering::SeqCst) {
+            if shutdown.stage() == Stage::PastDeadline {
                 println!("Ctrl-C, exiting for real");
                 std::process::exit(1);
             }
-            println!("Ctrl-C, entering lame duck mode");
-            running.store(false, Ordering::SeqCst);
+            match shutdown.request() {
+                Stage::LameDuck => println!(
+                    "Ctrl-C, entering lame duck mode for {}s, again to skip", grace),
+                _ => println!(
+                    "Ctrl-C, {:?} in the remaining games, again to exit for real", after_deadline),
+            }
         }
     }).unwrap();
+    let session_start = std::time::Instant::now();
 
     info!("**********************");
     println!("**********************");
 
-    if std::env::var("TELEG_BOT").is_err() {
-        println!("TELEG_BOT not set")
+    let notifications = match std::env::var("RBC_NOTIFY") {
+        Ok(spec) => Notifications::from_spec(&spec).unwrap_or_else(|e| {
+            eprintln!("RBC_NOTIFY: {}", e);
+            std::process::exit(1);
+        }),
+        Err(_) => Notifications::default(),
+    };
+    if notifications.is_empty() {
+        println!("RBC_NOTIFY not set, see src/notify.rs");
     }
+    let notifications = Arc::new(notifications);
 
     println!("accept invites: {}", accept_invites);
     println!("challenge threads: {}", max_challenge_threads);
 
-    std::panic::set_hook(Box::new(panic_hook));
+    // One search thread per game, so by default as many games as CPUs.
+    let max_games = match std::env::var("RBC_MAX_GAMES") {
+        Ok(s) => s.parse().ok().filter(|&n| n >= 1).unwrap_or_else(|| {
+            eprintln!("RBC_MAX_GAMES: {:?}", s);
+            std::process::exit(1);
+        }),
+        Err(_) => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    println!("max games: {}", max_games);
+    let mut throttle = Throttle::new(max_games);
+    let mut cpu = CpuMonitor::default();
+
+    std::panic::set_hook(Box::new({
+        let notifications = notifications.clone();
+        move |pi| panic_hook(pi, &notifications)
+    }));
 
     let (tx, rx) = std::sync::mpsc::channel();
 
     let mut slots: Vec<Option<Slot>> = Vec::new();
-
-    let spawn_thread = |slots: &mut Vec<Option<Slot>>, game_id, color, is_challenger: bool| {
+    let mut idle_notified = true;
+    let mut losing_streak = 0;
+
+    let mut opponents = OpponentPolicy::new(MY_NAME);
+    opponents.allow = env_list("RBC_ALLOW");
+    opponents.deny = match std::env::var("RBC_DENY") {
+        Ok(_) => env_list("RBC_DENY"),
+        Err(_) => Some("DotModus_Chris".to_owned()).into_iter().collect(),  // hangs
+    };
+    match rusqlite::Connection::open_with_flags("game_log.db", rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        Ok(conn) => match opponents.load_history(&conn, 2000) {
+            Ok(n) => println!("{} past games from game_log.db", n),
+            Err(e) => println!("game_log.db: {}", e),
+        }
+        Err(e) => println!("game_log.db: {}", e),
+    }
+    println!("deny: {:?}, allow: {:?}", opponents.deny, opponents.allow);
+
+    let shared = Arc::new(Shared {
+        dashboard: Mutex::new(Dashboard::default()),
+        opponents: Mutex::new(opponents),
+        shutdown,
+    });
+    let mut past_deadline = false;
+    // with AfterDeadline::Resign, so that games stuck in a search don't hold us up
+    let mut resigned = std::collections::HashSet::new();
+
+    let spawn_thread = |slots: &mut Vec<Option<Slot>>, game_id, color, opponent: Option<String>, is_challenger| {
+        let slot_opponent = opponent.clone();
         let slot_idx = slots.iter().position(Option::is_none).unwrap_or_else(|| {
             slots.push(None);
             slots.len() - 1
         });
         assert!(slots[slot_idx].is_none());
+        shared.dashboard.lock().unwrap().start_game(game_id, slot_idx, color, opponent);
 
         let t = std::thread::Builder::new()
         .name(format!("game_{}", game_id))
         .spawn({
             let ai = ai.clone();
             let tx = tx.clone();
+            let shared = shared.clone();
             move || {
                 ThreadLocalLogger::replace(Box::new(WriteLogger::new(
                     std::fs::File::create(format!("logs/game_{:05}.info.txt", game_id)).unwrap()
                 )));
-                let (outcome, message) = play_game_no_panic(color, game_id, &ai);
+                let (outcome, message) = play_game_no_panic(color, game_id, &ai, &shared);
+                log::logger().flush();
                 tx.send(slot_idx).unwrap();
                 (outcome, message)
             }
         }).unwrap();
-        slots[slot_idx] = Some(Slot { t, is_challenger });
+        slots[slot_idx] = Some(Slot { game_id, opponent: slot_opponent, t, is_challenger });
         slot_idx
     };
 
     loop {
-        if running.load(Ordering::SeqCst) {
+        if let Some(sample) = cpu.sample() {
+            if throttle.update(sample, num_games(&slots)) {
+                let msg = format!("max games: {} (cpu load {:.0}%)",
+                    throttle.limit(), 100.0 * throttle.load().unwrap());
+                info!("{}", msg);
+                println!("{}", msg);
+            }
+        }
+        if !past_deadline && shared.shutdown.stage() == Stage::PastDeadline {
+            past_deadline = true;
+            let msg = format!("shutdown deadline, {:?} in {} remaining games",
+                shared.shutdown.after_deadline, num_games(&slots));
+            info!("{}", msg);
+            println!("{}", msg);
+        }
+        if past_deadline && shared.shutdown.after_deadline == AfterDeadline::Resign {
+            for slot in slots.iter().flatten() {
+                if resigned.contains(&slot.game_id) {
+                    continue;
+                }
+                let done = match api::resign(slot.game_id) {
+                    Ok(()) => true,
+                    // the message can't be relied on, see api::HttpTransport
+                    Err(e) => match api::game_status(slot.game_id) {
+                        Ok(gs) if gs.is_over => true,
+                        _ => {
+                            // again in the next round
+                            report_error(&notifications, &shared.shutdown, &e);
+                            false
+                        }
+                    },
+                };
+                if done {
+                    info!("{}: resigned", slot.game_id);
+                    resigned.insert(slot.game_id);
+                }
+            }
+        }
+        if shared.shutdown.accepting_games() {
             if accept_invites {
-                api::announce_myself().expect("TODO");
-                for inv_id in api::list_invitations().expect("TODO") {
-                    let game_id = api::accept_invitation(inv_id).expect("TODO");
+                match api::announce_myself() {
+                    Ok(me) => if me.max_games != throttle.limit() as i32 {
+                        info!("max_games on the server: {}, setting {}", me.max_games, throttle.limit());
+                        if let Err(e) = api::set_max_games(throttle.limit() as i32) {
+                            report_error(&notifications, &shared.shutdown, &e);
+                        }
+                    }
+                    Err(e) => report_error(&notifications, &shared.shutdown, &e),
+                }
+                let invitations = api::list_invitations().unwrap_or_else(|e| {
+                    report_error(&notifications, &shared.shutdown, &e);
+                    Vec::new()
+                });
+                for inv_id in invitations {
+                    if num_games(&slots) >= throttle.limit() {
+                        break;
+                    }
+                    let game_id = match api::accept_invitation(inv_id) {
+                        Ok(game_id) => game_id,
+                        Err(e) => {
+                            report_error(&notifications, &shared.shutdown, &e);
+                            continue;
+                        }
+                    };
                     info!("{}: accepting invitation", game_id);
-                    let color = api::game_color(game_id).expect("TODO");
-                    let slot_idx = spawn_thread(&mut slots, game_id, color, false);
+                    let color = match api::game_color(game_id) {
+                        Ok(color) => color,
+                        Err(e) => {
+                            report_error(&notifications, &shared.shutdown, &e);
+                            continue;
+                        }
+                    };
+                    let opponent = match api::opponent_name(game_id) {
+                        Ok(name) => Some(name),
+                        Err(e) => {
+                            report_error(&notifications, &shared.shutdown, &e);
+                            None
+                        }
+                    };
+                    let slot_idx = spawn_thread(&mut slots, game_id, color, opponent.clone(), false);
+                    idle_notified = false;
                     print_slots(&slots, slot_idx, '_');
-                    println!("{}", game_id);
+                    println!("{}: {}", game_id, opponent.as_deref().unwrap_or("?"));
                 }
             }
             loop {
                 let num_challengers = slots.iter()
                     .filter(|s| s.as_ref().map_or(false, |s| s.is_challenger))
                     .count();
-                if num_challengers >= max_challenge_threads {
+                if num_challengers >= max_challenge_threads || num_games(&slots) >= throttle.limit() {
                     break;
                 }
-                let mut opponents = api::list_users().unwrap();
-                opponents.retain(|o|
-                    // o == "Oracle" &&
-                    o != "DotModus_Chris" &&  // hangs
-                    o != "genetic"
-                );
-                let opponent = rand::thread_rng().gen_range(0, opponents.len());
-                let opponent = &opponents[opponent];
-                let color: Color = rand::thread_rng().gen_bool(0.5).into();
-                let game_id = api::post_invitation(opponent, color).unwrap();
+                let users = match api::list_users() {
+                    Ok(users) => users,
+                    Err(e) => {
+                        report_error(&notifications, &shared.shutdown, &e);
+                        break;
+                    }
+                };
+                let choice = shared.opponents.lock().unwrap().choose(&users, &mut rand::thread_rng());
+                let (opponent, color) = match choice {
+                    Some(choice) => choice,
+                    None => {
+                        info!("nobody to challenge");
+                        break;
+                    }
+                };
+                let game_id = match api::post_invitation(&opponent, color) {
+                    Ok(game_id) => game_id,
+                    Err(e) => {
+                        report_error(&notifications, &shared.shutdown, &e);
+                        break;
+                    }
+                };
                 info!("challenger playing against {}", opponent);
-                let slot_idx = spawn_thread(&mut slots, game_id, color, true);
+                let slot_idx = spawn_thread(&mut slots, game_id, color, Some(opponent.clone()), true);
+                idle_notified = false;
                 print_slots(&slots, slot_idx, '.');
                 println!("{}: {}", game_id, opponent);
             }
         }
 
         if slots.iter().all(Option::is_none) {
-            if !running.load(Ordering::SeqCst) {
+            if !shared.shutdown.accepting_games() {
                 break;
             }
+            if !idle_notified {
+                notifications.notify(EventKind::SlotIdle, "no games in progress");
+                idle_notified = true;
+            }
             std::thread::sleep(std::time::Duration::from_secs(5));
         } else if let Ok(slot_idx) = rx.recv_timeout(std::time::Duration::from_secs(5)) {
             let slot = slots[slot_idx].take().unwrap();
             let (outcome, message) = slot.t.join().unwrap();
+            shared.dashboard.lock().unwrap().finish_game(slot.game_id, outcome);
+            if let (Some(opponent), 'E') = (&slot.opponent, outcome) {
+                shared.opponents.lock().unwrap().record_error(opponent);
+            }
             info!("{}", message);
             print_slots(&slots, slot_idx, outcome);
             println!("{}", message);
+            match outcome {
+                'L' => losing_streak += 1,
+                'W' | 'D' => losing_streak = 0,
+                _ => {}
+            }
+            if losing_streak >= 3 {
+                notifications.notify(EventKind::GameFinished,
+                    &format!("{} ({} losses in a row)", message, losing_streak));
+            } else {
+                notifications.notify(EventKind::GameFinished, &message);
+            }
 
             while slots.len() > 5 && slots.last().unwrap().is_none() {
                 slots.pop();
             }
         }
+        if let Err(e) = shared.dashboard.lock().unwrap().write_to(STATUS_PAGE) {
+            error!("{}: {}", STATUS_PAGE, e);
+        }
     }
-    info!("finished");
-    println!("finished");
+    let summary = format!("finished after {:.0}s, {}",
+        session_start.elapsed().as_secs_f64(), shared.dashboard.lock().unwrap().summary());
+    info!("{}", summary);
+    println!("{}", summary);
+    log::logger().flush();
 }
@@ -12,8 +12,8 @@ fn build_dict(game_ids: &[i32]) -> Vec<u8> {
         pb.lock().unwrap().inc();
         match api::game_history_raw(game_id) {
             Ok(h) => Some(h),
-            Err(api::Error::HttpError(400)) |
-            Err(api::Error::HttpError(404)) => None,
+            // not found, or not finished yet
+            Err(e) if matches!(e.status(), Some(400) | Some(404)) => None,
             e => panic!("{:?}", e),
         }
     });
@@ -81,8 +81,8 @@ fn main() {
                     zd,
                 ))
             }
-            Err(api::Error::HttpError(400)) |
-            Err(api::Error::HttpError(404)) => None,
+            // not found, or not finished yet
+            Err(e) if matches!(e.status(), Some(400) | Some(404)) => None,
             e => { dbg!(&e); e.unwrap(); unreachable!(); }
         }
     });
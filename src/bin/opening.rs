@@ -1,12 +1,15 @@
-use rbc::cfr::{Encoding, Cfr};
+use rbc::cfr::{Encoding, Cfr, Solver, Variant};
+use rbc::mccfr::{Mccfr, Sampling};
+use rbc::cfr_io::StrategyBook;
 use rbc::game::{Color, BoardState};
 
-use rbc::rbc_xf::{State, RbcGame};
+use rbc::rbc_xf::{State, RbcGame, SearchEvaluator};
 
 fn main() {
     let mut ctx = rbc::eval::Ctx::new(BoardState::initial());
     ctx.expensive_eval = true;
-    let mut rbc_game = RbcGame::new(1 + 4, 3, &mut ctx, State::ChoosePositionBeforeMove(Color::Black), vec![
+    let mut evaluator = SearchEvaluator::new(&mut ctx, 3);
+    let mut rbc_game = RbcGame::new(1 + 4, &mut evaluator, State::ChoosePositionBeforeMove(Color::Black), vec![
         BoardState::initial(),
         fen::BoardState::from_fen("rnbqkb1r/pppppppp/8/8/8/5n2/PPPPPPPP/RNBQKBNR w KQkq - 0 0").unwrap().into(),
         fen::BoardState::from_fen("rnbqkb1r/pppppppp/8/8/8/3n4/PPPPPPPP/RNBQKBNR w KQkq - 0 0").unwrap().into(),
@@ -15,42 +18,104 @@ fn main() {
     let enc = Encoding::new(&mut rbc_game);
     println!("it took {:.3}s", timer.elapsed().as_secs_f64());
     // dbg!(&enc);
-    dbg!(enc.nodes.len());
+    dbg!(enc.num_nodes());
+    println!("{:.1} bytes per node", enc.heap_size() as f64 / enc.num_nodes() as f64);
     dbg!(enc.infosets.len());
-    dbg!(rbc_game.eval_cache.len());
     /*for inf in &enc.infosets {
         println!("{} {:?}", inf.player, inf.orig);
         println!("    {:?}", inf.actions);
     }*/
     // return;
+    // Convergence curves: value and nash conv of the average strategy profile
+    // after a given number of iterations.
+    let solvers = [
+        ("vanilla", Variant::Vanilla, false),
+        ("vanilla alt", Variant::Vanilla, true),
+        ("cfr+", Variant::CfrPlus, true),
+        ("linear", Variant::Linear, true),
+        ("dcfr", Variant::DCFR, true),
+    ];
+    let checkpoints = [1, 3, 10, 30, 100, 300, 1000, 3000, 10_000, 30_000];
     let mut cfr = Cfr::new(&enc);
-    // dbg!(&cfr);
-    let timer = std::time::Instant::now();
-    for step in 0..30_000 {
-        cfr.step(&enc);
-        if (step + 1) % 10_000 == 0 {
-            let mut strat: Vec<_> = cfr.get_strategy(&enc).into_iter().collect();
-            strat.sort_by_key(|(infoset, _)| (infoset.len(), format!("{:?}", infoset)));
-            for (infoset, mut ss) in strat {
-                if infoset.len() >= 3 {
-                    break;
-                }
-                println!("{:?}", infoset);
-                println!("    // ev={}, visit_prob={}", ss.expected_value, ss.visit_prob);
-                ss.actions.sort_by(|(_, p1), (_, p2)| p2.partial_cmp(p1).unwrap());
-                for (a, p) in ss.actions {
-                    if p < 1e-3 {
-                        println!("   ...");
-                        break;
-                    }
-                    println!("    {:>5.3} {:?}", p, a);
-                }
+    for &(name, variant, alternating) in &solvers {
+        cfr = Cfr::with_variant(&enc, variant);
+        cfr.alternating = alternating;
+        let timer = std::time::Instant::now();
+        let mut steps = 0;
+        for &c in &checkpoints {
+            while steps < c {
+                cfr.step(&enc);
+                steps += 1;
             }
-            println!("----------------");
+            let t = timer.elapsed().as_secs_f64();
+            let nc = rbc::cfr::nash_conv(&enc, &cfr.average_strategy(&enc));
+            println!("{:>12} {:>6} {:>8.3}s {:>9.4} {:>9.4}", name, steps, t, cfr.average_value(&enc), nc);
         }
+        println!();
+    }
+
+    #[cfg(feature = "heavy")]
+    {
+        let mut par = rbc::cfr::ParallelCfr::with_variant(&enc, Variant::Linear);
+        let timer = std::time::Instant::now();
+        let mut steps = 0;
+        for &c in &checkpoints[..checkpoints.len() - 2] {
+            while steps < c {
+                par.step(&enc);
+                steps += 1;
+            }
+            let t = timer.elapsed().as_secs_f64();
+            let nc = rbc::cfr::nash_conv(&enc, &par.average_strategy(&enc));
+            println!("{:>12} {:>6} {:>8.3}s {:>9.4} {:>9.4}", "linear par", steps, t, par.average_value(&enc), nc);
+        }
+        println!();
     }
-    println!("it took {:.3}s", timer.elapsed().as_secs_f64());
-    // dbg!(cfr.get_strategy(&enc));
 
-    // let mut openins = openin
+    // MCCFR expands the tree lazily, so it does not need the Encoding,
+    // here it's only used to measure nash conv.
+    for &(name, sampling) in &[
+        ("external", Sampling::External),
+        ("outcome", Sampling::Outcome { exploration: 0.6 }),
+    ] {
+        let mut mccfr = Mccfr::new(sampling, 42);
+        let timer = std::time::Instant::now();
+        let mut steps = 0;
+        for &c in &checkpoints[..checkpoints.len() - 2] {
+            while steps < c {
+                mccfr.step(&mut rbc_game);
+                steps += 1;
+            }
+            let t = timer.elapsed().as_secs_f64();
+            let nc = rbc::cfr::nash_conv(&enc, &mccfr.average_strategy(&enc));
+            println!("{:>12} {:>6} {:>8.3}s {:>9} {:>9.4}  {} infosets, {} nodes visited",
+                name, steps, t, "", nc, mccfr.num_infosets(), mccfr.nodes_visited);
+        }
+        println!();
+    }
+
+    // Keep the results for players to look up at game time.
+    std::fs::create_dir_all("data").unwrap();
+    rbc::cfr_io::save_encoding(&enc, "data/opening.enc").unwrap();
+    let book = StrategyBook::from_average_strategy(&enc, &cfr.average_strategy(&enc));
+    book.save("data/opening.strategy").unwrap();
+    println!("saved {} infosets to data/", book.len());
+
+    // Strategy found by the last solver.
+    let mut strat: Vec<_> = cfr.get_strategy(&enc).into_iter().collect();
+    strat.sort_by_key(|(infoset, _)| (infoset.len(), format!("{:?}", infoset)));
+    for (infoset, mut ss) in strat {
+        if infoset.len() >= 3 {
+            break;
+        }
+        println!("{:?}", infoset);
+        println!("    // ev={}, visit_prob={}", ss.expected_value, ss.visit_prob);
+        ss.actions.sort_by(|(_, p1), (_, p2)| p2.partial_cmp(p1).unwrap());
+        for (a, p) in ss.actions {
+            if p < 1e-3 {
+                println!("   ...");
+                break;
+            }
+            println!("    {:>5.3} {:?}", p, a);
+        }
+    }
 }
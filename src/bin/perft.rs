@@ -0,0 +1,71 @@
+use rbc::game::{STARTING_FEN, BoardState};
+use rbc::perft;
+
+fn usage() -> ! {
+    eprintln!("usage:");
+    eprintln!("    perft <depth> [<fen>]    per-move node counts (fast generator)");
+    eprintln!("    perft naive <depth> [<fen>]    same, but with moves.rs generator");
+    eprintln!("    perft check <depth>    cross-check generators on the fixture positions");
+    std::process::exit(1);
+}
+
+fn parse_depth(s: &str) -> u32 {
+    match s.parse() {
+        Ok(d) if d >= 1 => d,
+        _ => usage(),
+    }
+}
+
+fn main() {
+    rbc::logger::init_changeable_logger(rbc::logger::SimpleLogger);
+    log::set_max_level(log::LevelFilter::Warn);  // fast::verify() is chatty at Info
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["check", depth] => {
+            let depth = parse_depth(depth);
+            for f in perft::parse_fixtures(perft::POSITIONS) {
+                let b: BoardState = fen::BoardState::from_fen(&f.fen).unwrap().into();
+                let timer = std::time::Instant::now();
+                perft::cross_check(&b, depth);
+                for (i, &expected) in f.counts.iter().enumerate() {
+                    let d = i as u32 + 1;
+                    if d > depth {
+                        break;
+                    }
+                    let actual = perft::perft_naive(&b, d);
+                    assert_eq!(actual, expected, "{} depth {}", f.fen, d);
+                }
+                println!("ok {:>6.2}s  {}", timer.elapsed().as_secs_f64(), f.fen);
+            }
+        }
+        ["naive", depth] | ["naive", depth, _] => {
+            let depth = parse_depth(depth);
+            let b: BoardState = fen::BoardState::from_fen(args.get(2).map_or(STARTING_FEN, String::as_str)).unwrap().into();
+            let timer = std::time::Instant::now();
+            let mut total = 0;
+            for m in b.all_moves() {
+                let mut b2 = b.clone();
+                b2.make_move(m);
+                let cnt = perft::perft_naive(&b2, depth - 1);
+                println!("{:>6} {}", m.map_or("pass".to_owned(), |m| m.to_uci()), cnt);
+                total += cnt;
+            }
+            println!("total {} in {:.3}s", total, timer.elapsed().as_secs_f64());
+        }
+        [depth] | [depth, _] => {
+            let depth = parse_depth(depth);
+            let b: BoardState = fen::BoardState::from_fen(args.get(1).map_or(STARTING_FEN, String::as_str)).unwrap().into();
+            let timer = std::time::Instant::now();
+            let d = perft::divide(&b, depth);
+            let mut total = 0;
+            for (m, cnt) in d {
+                println!("{:>6} {}", m.map_or("pass".to_owned(), |m| m.to_uci()), cnt);
+                total += cnt;
+            }
+            let t = timer.elapsed().as_secs_f64();
+            println!("total {} in {:.3}s ({:.0} ns per node)", total, t, 1e9 * t / total.max(1) as f64);
+        }
+        _ => usage(),
+    }
+}
@@ -5,6 +5,7 @@ use rand::prelude::*;
 use rbc::game::{Square, Color, Move, Piece, BoardState};
 use rbc::ai_interface::{Ai, Player};
 use rbc::infoset::Infoset;
+use rbc::session::Outcome;
 use rbc::distr;
 
 struct GameState {
@@ -23,9 +24,10 @@ impl GameState {
         GameState {
             board: BoardState::initial(),
             infoset_white: Infoset::new(Color::White),
-            player_white: ai_white.make_player(Color::White, seed + 1),
+            // 900s clocks without increment, see phase1()
+            player_white: ai_white.make_player(Color::White, seed + 1, 0.0),
             infoset_black: Infoset::new(Color::Black),
-            player_black: ai_black.make_player(Color::Black, seed + 2),
+            player_black: ai_black.make_player(Color::Black, seed + 2, 0.0),
             move_number: 0,
             last_capture: None,
             timer: std::time::Instant::now(),
@@ -81,28 +83,60 @@ impl GameState {
 
         self.move_number += 1;
     }
+
+    // Games are played out, Player::should_resign() isn't asked.
+    // No GameHistory, the html logs are the record of self-play games.
+    fn finish(&mut self, html_white: &mut dyn Write, html_black: &mut dyn Write) {
+        let outcome = Outcome {
+            winner: self.board.winner(),
+            reason: "KING_CAPTURE".to_owned(),
+            history: None,
+        };
+        self.player_white.game_over(&outcome, html_white);
+        self.player_black.game_over(&outcome, html_black);
+    }
 }
 
+// CFR in the experimental player stops once both sides together could
+// gain less than this by deviating. In eval units, where a pawn is 100,
+// so a tenth of a pawn: well below what the leaf evaluation can tell apart.
+const CFR_TARGET_NASH_CONV: f32 = 10.0;
+
 fn main() {
     rbc::logger::init_changeable_logger(
         rbc::logger::WriteLogger::new(
             std::fs::File::create("logs/self_play.info.txt").unwrap()));
     log::set_max_level(log::LevelFilter::Info);
 
-    let ai1 = rbc::greedy::GreedyAi { experiment: true };
-    let ai2 = rbc::greedy::GreedyAi { experiment: false };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let ai1: Box<dyn Ai> = if args.iter().any(|a| a == "ismcts") {
+        Box::new(rbc::ismcts::IsmctsAi::default())
+    } else {
+        Box::new(rbc::greedy::GreedyAi {
+            experiment: true,
+            cfr_variant: rbc::cfr::Variant::DCFR,
+            cfr_target_nash_conv: Some(CFR_TARGET_NASH_CONV),
+            cfr_parallel: true,
+        })
+    };
+    let ai2 = rbc::greedy::GreedyAi {
+        experiment: false,
+        cfr_variant: rbc::cfr::Variant::Vanilla,
+        cfr_target_nash_conv: None,
+        cfr_parallel: false,
+    };
+    let ai1 = &*ai1;
 
     let mut rng = StdRng::seed_from_u64(424242);
 
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() == 2 && args[1] == "bench" {
+    if args.iter().any(|a| a == "bench") {
         let mut html_white = std::io::BufWriter::new(
             std::fs::File::create("logs/self_play_white.html").unwrap());
         writeln!(html_white, "{}", rbc::html::PREAMBLE).unwrap();
         let mut html_black = std::io::sink();
 
         let timer = std::time::Instant::now();
-        let mut game = GameState::new(&ai1, &ai2, 424242);
+        let mut game = GameState::new(ai1, &ai2, 424242);
         while !game.is_over() {
             dbg!(game.move_number);
             let html: &mut dyn Write = match game.board.side_to_play() {
@@ -115,6 +149,7 @@ fn main() {
             let requested = *distr::draw(&requested_distr, &mut rng);
             game.phase3(requested, html);
         }
+        game.finish(&mut html_white, &mut html_black);
         println!("{:?} won", game.board.winner().unwrap());
         println!("{:#?}", game.board.render());
         println!("white summary:\n{}", game.player_white.get_summary());
@@ -129,8 +164,8 @@ fn main() {
     use std::collections::HashMap;
     let mut outcome_cnt: HashMap<(Color, Color), i32> = HashMap::new();
     loop {
-        let mut game1 = GameState::new(&ai1, &ai2, 424242);
-        let mut game2 = GameState::new(&ai2, &ai1, 424242);
+        let mut game1 = GameState::new(ai1, &ai2, 424242);
+        let mut game2 = GameState::new(&ai2, ai1, 424242);
         while !game1.is_over() && !game2.is_over() {
             // println!("{} both", game1.move_number);
             let mut sense_distr1 = game1.phase1(&mut html);
@@ -165,6 +200,8 @@ fn main() {
             let requested = *distr::draw(&requested_distr, &mut rng);
             game2.phase3(requested, &mut html);
         }
+        game1.finish(&mut html, &mut std::io::sink());
+        game2.finish(&mut html, &mut std::io::sink());
         println!("moves: {} {}", game1.move_number, game2.move_number);
         let outcome = (game1.board.winner().unwrap(), game2.board.winner().unwrap());
         println!("outcome: {:?}", outcome);
@@ -12,6 +12,20 @@ fn main() {
     let timer = std::time::Instant::now();
     let mut ctx = rbc::eval::Ctx::new(board.clone());
     ctx.expensive_eval = true;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "all" => ctx.enable_all_enhancements(),
+            "killers" => ctx.killer_moves = true,
+            "history" => ctx.history_heuristic = true,
+            "null" => ctx.null_move_pruning = true,
+            "lmr" => ctx.late_move_reductions = true,
+            "checkext" => ctx.check_extensions = true,
+            _ => {
+                eprintln!("usage: tree [all|killers|history|null|lmr|checkext]...");
+                std::process::exit(1);
+            }
+        }
+    }
     for depth in 0..7 {
         ctx.reset(board.clone());
         let timer = std::time::Instant::now();
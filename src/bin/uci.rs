@@ -0,0 +1,198 @@
+// UCI-compatible front end for eval::search(), for watching the engine
+// think in chess GUIs and analysis tools.
+//
+// Deviations from standard chess, following RBC rules:
+// - There is no check, checkmate or stalemate. Moves leaving the king
+//   en prise are legal, and castling out of, through or into check is legal.
+//   GUIs that validate moves will reject some of the engine's choices.
+// - The game ends when a king is captured. Scores within 100 of +-10000
+//   are reported as "mate N", where N counts moves until the king capture.
+// - Passing is legal and is sent and accepted as the null move "0000".
+// - Positions with a missing king are accepted; "go" then reports
+//   "bestmove 0000".
+//
+// Supported commands: uci, isready, ucinewgame, position, go (depth,
+// movetime, wtime/btime/winc/binc, infinite), stop, quit.
+// Debug tracing (tree_println!) is not available through this front end.
+
+use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use rbc::game::{Color, Move, BoardState};
+use rbc::eval::{Ctx, Stats};
+
+const MAX_DEPTH: i32 = 64;
+
+fn parse_position(args: &[&str]) -> Result<BoardState, String> {
+    let (mut board, rest): (BoardState, &[&str]) = match args {
+        ["startpos", rest @ ..] => (BoardState::initial(), rest),
+        ["fen", rest @ ..] => {
+            let n = rest.iter().position(|&a| a == "moves").unwrap_or(rest.len());
+            let fen = rest[..n].join(" ");
+            let b = fen::BoardState::from_fen(&fen).map_err(|e| format!("bad fen {:?}: {:?}", fen, e))?;
+            (b.into(), &rest[n..])
+        }
+        _ => return Err(format!("bad position command {:?}", args)),
+    };
+    let moves = match rest {
+        [] => &[][..],
+        ["moves", moves @ ..] => moves,
+        _ => return Err(format!("bad position command {:?}", args)),
+    };
+    for &m in moves {
+        let m = if m == "0000" {
+            None
+        } else if m.len() == 4 || m.len() == 5 {
+            Some(Move::from_uci(m))
+        } else {
+            return Err(format!("bad move {:?}", m));
+        };
+        if board.winner().is_some() || !board.all_moves().contains(&m) {
+            return Err(format!("illegal move {:?}", m));
+        }
+        board.make_move(m);
+        board.clear_irrelevant_en_passant_square();
+    }
+    Ok(board)
+}
+
+struct GoParams {
+    depth: i32,
+    movetime: Option<f64>,
+}
+
+fn parse_go(args: &[&str], side: Color) -> GoParams {
+    let mut depth = MAX_DEPTH;
+    let mut movetime = None;
+    let mut time_left = None;
+    let mut inc = 0.0;
+    let mut it = args.iter();
+    while let Some(&a) = it.next() {
+        let mut num = || it.next().and_then(|x| x.parse::<f64>().ok());
+        match (a, side) {
+            ("depth", _) => depth = num().map_or(MAX_DEPTH, |d| d as i32),
+            ("movetime", _) => movetime = num().map(|ms| ms / 1000.0),
+            ("wtime", Color::White) | ("btime", Color::Black) => time_left = num().map(|ms| ms / 1000.0),
+            ("winc", Color::White) | ("binc", Color::Black) => inc = num().map_or(0.0, |ms| ms / 1000.0),
+            ("wtime", _) | ("btime", _) | ("winc", _) | ("binc", _) |
+            ("movestogo", _) | ("nodes", _) | ("mate", _) => { num(); }
+            _ => {}  // infinite, ponder
+        }
+    }
+    if movetime.is_none() {
+        if let Some(t) = time_left {
+            movetime = Some(t / 30.0 + inc * 0.8);
+        }
+    }
+    GoParams { depth, movetime }
+}
+
+fn format_score(score: i32) -> String {
+    if score.abs() >= 10000 - 100 {
+        let plies = 10000 - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+fn think(mut ctx: Ctx, board: BoardState, params: GoParams) -> Ctx {
+    let timer = std::time::Instant::now();
+    ctx.reset(board.clone());
+    ctx.stats = Stats::default();
+    let mut best_move = None;
+    if board.winner().is_none() {
+        for depth in 1..=params.depth {
+            let score = rbc::eval::search(depth, -10000, 10000, &mut ctx);
+            if ctx.aborted() {
+                break;
+            }
+            let t = timer.elapsed().as_secs_f64();
+            let pv = &ctx.pvs[0];
+            best_move = pv.first().cloned();
+            println!("info depth {} score {} nodes {} time {} nps {} pv {}",
+                depth, format_score(score),
+                ctx.stats.nodes, (t * 1000.0) as i64,
+                (ctx.stats.nodes as f64 / t.max(1e-3)) as i64,
+                pv.iter().map(|m| m.to_uci()).collect::<Vec<_>>().join(" "));
+            if score.abs() >= 10000 - 100 {
+                break;
+            }
+            if let Some(mt) = params.movetime {
+                // the next iteration typically takes several times longer
+                if t > 0.4 * mt {
+                    break;
+                }
+            }
+        }
+    }
+    println!("bestmove {}", best_move.map_or("0000".to_owned(), |m: Move| m.to_uci()));
+    ctx
+}
+
+fn main() {
+    let mut board = BoardState::initial();
+    let mut idle_ctx = Some(Ctx::new(board.clone()));
+    idle_ctx.as_mut().unwrap().expensive_eval = true;
+    // The search thread owns Ctx while thinking and hands it back when done.
+    let mut search: Option<(Arc<AtomicBool>, std::thread::JoinHandle<Ctx>)> = None;
+
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens[..] {
+            ["uci"] => {
+                println!("id name rbc");
+                println!("id author Vlad Shcherbina");
+                println!("uciok");
+            }
+            ["isready"] => println!("readyok"),
+            ["ucinewgame"] => {
+                if let Some((stop, t)) = search.take() {
+                    stop.store(true, Ordering::SeqCst);
+                    t.join().unwrap();
+                }
+                let mut ctx = Ctx::new(BoardState::initial());
+                ctx.expensive_eval = true;
+                idle_ctx = Some(ctx);
+            }
+            ["position", ref args @ ..] => {
+                match parse_position(args) {
+                    Ok(b) => board = b,
+                    Err(e) => println!("info string {}", e),
+                }
+            }
+            ["go", ref args @ ..] => {
+                if let Some((stop, t)) = search.take() {
+                    stop.store(true, Ordering::SeqCst);
+                    idle_ctx = Some(t.join().unwrap());
+                }
+                let params = parse_go(args, board.side_to_play());
+                let mut ctx = idle_ctx.take().unwrap();
+                // fresh flag per search, so a stale movetime timer can't stop the next one
+                let stop = Arc::new(AtomicBool::new(false));
+                ctx.stop = stop.clone();
+                if let Some(mt) = params.movetime {
+                    let stop = stop.clone();
+                    std::thread::spawn(move || {
+                        std::thread::sleep(std::time::Duration::from_secs_f64(mt));
+                        stop.store(true, Ordering::SeqCst);
+                    });
+                }
+                let board = board.clone();
+                search = Some((stop, std::thread::spawn(move || think(ctx, board, params))));
+            }
+            ["stop"] => {
+                if let Some((stop, t)) = search.take() {
+                    stop.store(true, Ordering::SeqCst);
+                    idle_ctx = Some(t.join().unwrap());
+                }
+            }
+            ["quit"] => break,
+            [] => {}
+            _ => println!("info string unknown command {:?}", line),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 #[derive(Debug)]
 pub enum NodeInfo<Action, Infoset> {
@@ -11,14 +12,22 @@ pub enum NodeInfo<Action, Infoset> {
     }
 }
 
+// Game tree is walked by stepping a state forward:
+// traversals clone the state before applying each action,
+// so implementations don't have to replay the history for every node.
+// Games that are easier to describe in terms of the action history
+// can use Vec<Action> as their State.
 pub trait Game: Sized {
     type Action: Clone + Eq + std::fmt::Debug;
     type Infoset: Clone + Eq + std::hash::Hash + std::fmt::Debug;
+    type State: Clone;
 
-    fn node_info(&mut self, h: &[Self::Action]) -> NodeInfo<Self::Action, Self::Infoset>;
+    fn initial_state(&mut self) -> Self::State;
+    fn node_info(&mut self, s: &Self::State) -> NodeInfo<Self::Action, Self::Infoset>;
+    fn apply(&mut self, s: &mut Self::State, a: &Self::Action);
 }
 
-type CompactNode = usize;
+pub type CompactNode = u32;
 
 #[derive(Debug)]
 pub struct CompactInfoset<OrigAction, OrigInfoset>
@@ -27,17 +36,43 @@ pub struct CompactInfoset<OrigAction, OrigInfoset>
     pub player: usize,
     pub actions: Vec<OrigAction>,
 
-    observable_history: Vec<(usize, usize)>,
+    pub(crate) observable_history: Vec<(usize, usize)>,
     // Pairs (infoset, action) for all past choices by this player.
     // To check perfect recall property.
 }
 
+pub(crate) const TERMINAL: u32 = 2;
+pub(crate) const CHANCE: u32 = 3;
+
+// kind is the player for choice nodes, TERMINAL or CHANCE.
+// payload is the value bits for terminal nodes, the start of
+// the probabilities in Encoding::probs for chance nodes and the infoset
+// for choice nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct NodeRecord {
+    pub(crate) kind: u32,
+    pub(crate) payload: u32,
+    pub(crate) children_start: u32,
+    pub(crate) num_children: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a> {
+    Terminal(f32),
+    Chance { probs: &'a [f32], children: &'a [CompactNode] },
+    Choice { player: usize, infoset: usize, children: &'a [CompactNode] },
+}
+
+// Nodes are stored flat: a fixed size record per node, with the children
+// of each node in a contiguous range of a shared array.
 #[derive(Debug)]
 pub struct Encoding<G: Game> {
     pub infoset_by_orig: HashMap<G::Infoset, usize>,
     pub infosets: Vec<CompactInfoset<G::Action, G::Infoset>>,
-    pub nodes: Vec<NodeInfo</*Action*/CompactNode, /*Infoset*/usize>>,
-    pub parents: Vec<Option<(usize, G::Action)>>,
+    pub(crate) nodes: Vec<NodeRecord>,
+    pub(crate) children: Vec<CompactNode>,
+    pub(crate) probs: Vec<f32>,
+    pub parents: Vec<Option<(CompactNode, G::Action)>>,
     pub root: CompactNode,
 }
 
@@ -47,34 +82,90 @@ impl<G: Game> Encoding<G> {
             infoset_by_orig: HashMap::new(),
             infosets: Vec::new(),
             nodes: Vec::new(),
+            children: Vec::new(),
+            probs: Vec::new(),
             parents: Vec::new(),
             root: 42,
         };
-        enc.root = enc.translate_node(g, &mut Vec::new(), &mut [Vec::new(), Vec::new()], None);
+        let s = g.initial_state();
+        enc.root = enc.translate_node(g, &s, &mut [Vec::new(), Vec::new()], None);
+        enc.nodes.shrink_to_fit();
+        enc.children.shrink_to_fit();
+        enc.probs.shrink_to_fit();
+        enc.parents.shrink_to_fit();
         enc
     }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, node: CompactNode) -> NodeRef<'_> {
+        let r = self.nodes[node as usize];
+        let start = r.children_start as usize;
+        let children = &self.children[start..start + r.num_children as usize];
+        match r.kind {
+            TERMINAL => NodeRef::Terminal(f32::from_bits(r.payload)),
+            CHANCE => {
+                let p = r.payload as usize;
+                NodeRef::Chance { probs: &self.probs[p..p + children.len()], children }
+            }
+            player => NodeRef::Choice { player: player as usize, infoset: r.payload as usize, children },
+        }
+    }
+
+    // Frees what's only needed for construction and debugging:
+    // parents, infoset_by_orig and the perfect recall check data.
+    pub fn strip_debug_info(&mut self) {
+        self.parents = Vec::new();
+        self.infoset_by_orig = HashMap::new();
+        for inf in &mut self.infosets {
+            inf.observable_history = Vec::new();
+        }
+    }
+
+    // Approximate heap usage of the tree structure, not counting
+    // what G::Action and G::Infoset own.
+    pub fn heap_size(&self) -> usize {
+        use std::mem::size_of;
+        self.nodes.capacity() * size_of::<NodeRecord>()
+            + self.children.capacity() * size_of::<CompactNode>()
+            + self.probs.capacity() * size_of::<f32>()
+            + self.parents.capacity() * size_of::<Option<(CompactNode, G::Action)>>()
+            + self.infoset_by_orig.capacity() * (size_of::<G::Infoset>() + size_of::<usize>())
+            + self.infosets.iter().map(|inf| {
+                size_of::<CompactInfoset<G::Action, G::Infoset>>()
+                    + inf.actions.capacity() * size_of::<G::Action>()
+                    + inf.observable_history.capacity() * size_of::<(usize, usize)>()
+            }).sum::<usize>()
+    }
+
     fn translate_node(
         &mut self,
         g: &mut G,
-        h: &mut Vec<G::Action>,
+        s: &G::State,
         obs_history: &mut [Vec<(usize, usize)>; 2],
         parent: Option<(CompactNode, G::Action)>,
     ) -> CompactNode {
-        self.nodes.push(NodeInfo::Terminal(42.0));
+        let result = CompactNode::try_from(self.nodes.len()).expect("too many nodes");
+        self.nodes.push(NodeRecord { kind: TERMINAL, payload: 0, children_start: 0, num_children: 0 });
         self.parents.push(parent);
-        let result = self.nodes.len() - 1;
-        match g.node_info(h) {
+        let record = match g.node_info(s) {
             NodeInfo::Terminal(x) => {
-                self.nodes[result] = NodeInfo::Terminal(x);
+                NodeRecord { kind: TERMINAL, payload: x.to_bits(), children_start: 0, num_children: 0 }
             }
             NodeInfo::Chance(probs) => {
-                let mut chance = Vec::with_capacity(probs.len());
+                let mut children = Vec::with_capacity(probs.len());
+                let mut ps = Vec::with_capacity(probs.len());
                 for (prob, action) in probs {
-                    h.push(action.clone());
-                    chance.push((prob, self.translate_node(g, h, obs_history, Some((result, action)))));
-                    h.pop().unwrap();
+                    let mut s2 = s.clone();
+                    g.apply(&mut s2, &action);
+                    ps.push(prob);
+                    children.push(self.translate_node(g, &s2, obs_history, Some((result, action))));
                 }
-                self.nodes[result] = NodeInfo::Chance(chance);
+                let payload = self.probs.len() as u32;
+                self.probs.extend(ps);
+                self.add_children(CHANCE, payload, children)
             }
             NodeInfo::Choice { player, infoset, actions } => {
                 let infosets = &mut self.infosets;
@@ -92,24 +183,27 @@ impl<G: Game> Encoding<G> {
                 assert_eq!(ci.actions, actions);
                 assert_eq!(ci.observable_history, obs_history[player], "perfect recall property violated");
 
-                let mut choice_actions = Vec::with_capacity(actions.len());
+                let mut children = Vec::with_capacity(actions.len());
                 for (action_idx, action) in actions.into_iter().enumerate() {
-                    h.push(action.clone());
+                    let mut s2 = s.clone();
+                    g.apply(&mut s2, &action);
                     obs_history[player].push((infoset_idx, action_idx));
-                    choice_actions.push(self.translate_node(g, h, obs_history, Some((result, action))));
+                    children.push(self.translate_node(g, &s2, obs_history, Some((result, action))));
                     obs_history[player].pop().unwrap();
-                    h.pop();
                 }
-
-                self.nodes[result] = NodeInfo::Choice {
-                    player: player,
-                    infoset: infoset_idx,
-                    actions: choice_actions,
-                };
+                self.add_children(player as u32, infoset_idx as u32, children)
             }
-        }
+        };
+        self.nodes[result as usize] = record;
         result
     }
+
+    fn add_children(&mut self, kind: u32, payload: u32, children: Vec<CompactNode>) -> NodeRecord {
+        let children_start = self.children.len() as u32;
+        let num_children = children.len() as u32;
+        self.children.extend(children);
+        NodeRecord { kind, payload, children_start, num_children }
+    }
 }
 
 #[derive(Debug)]
@@ -135,9 +229,42 @@ impl CfrEntry {
     }
 }
 
+// Common interface for equilibrium solvers working on an Encoding.
+pub trait Solver<G: Game> {
+    fn step(&mut self, enc: &Encoding<G>);
+    // Expected value (for player 0) of the average strategy profile.
+    fn average_value(&mut self, enc: &Encoding<G>) -> f32;
+    fn get_strategy(&mut self, enc: &Encoding<G>) -> HashMap<G::Infoset, InfosetStrategy<G::Action>>;
+    // Action probabilities of the average strategy, indexed like enc.infosets.
+    fn average_strategy(&mut self, enc: &Encoding<G>) -> Vec<Vec<f32>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    Vanilla,
+    // Regrets are floored at zero after each update,
+    // average strategy is weighted by iteration number.
+    CfrPlus,
+    // Regrets and average strategy are weighted by iteration number.
+    Linear,
+    // DCFR (Brown, Sandholm 2019). After iteration t, positive regrets are
+    // multiplied by t^alpha / (t^alpha + 1), negative ones by
+    // t^beta / (t^beta + 1), and the average strategy by (t / (t + 1))^gamma.
+    Discounted { alpha: f32, beta: f32, gamma: f32 },
+}
+
+impl Variant {
+    pub const DCFR: Variant = Variant::Discounted { alpha: 1.5, beta: 0.0, gamma: 2.0 };
+}
+
 #[derive(Debug)]
 pub struct Cfr {
     entries: Vec<CfrEntry>,
+    variant: Variant,
+    // Update one player per traversal, letting the second one
+    // respond to the already updated strategy of the first.
+    pub alternating: bool,
+    iteration: u32,
 }
 
 #[derive(Debug)]
@@ -147,25 +274,33 @@ pub struct InfosetStrategy<Action> {
     pub visit_prob: f32,
 }
 
-impl Cfr {
-    pub fn new<G: Game>(enc: &Encoding<G>) -> Self {
-        Cfr {
-            entries: enc.infosets.iter().map(|i| CfrEntry::new(i.actions.len())).collect(),
+impl<G: Game> Solver<G> for Cfr {
+    fn step(&mut self, enc: &Encoding<G>) {
+        self.iteration += 1;
+        if self.alternating {
+            for player in 0..2 {
+                self.compute_cur_sigma();
+                self.visit(enc, enc.root, [1.0, 1.0], 1.0, Some(player));
+                self.discount(enc, Some(player));
+            }
+        } else {
+            self.compute_cur_sigma();
+            self.visit(enc, enc.root, [1.0, 1.0], 1.0, None);
+            self.discount(enc, None);
         }
     }
 
-    pub fn step<G: Game>(&mut self, enc: &Encoding<G>) {
-        self.compute_cur_sigma();
-        self.visit(enc, enc.root, [1.0, 1.0], 1.0);
-    }
-
-    pub fn get_strategy<G: Game>(&mut self, enc: &Encoding<G>) -> HashMap<G::Infoset, InfosetStrategy<G::Action>> {
+    fn average_value(&mut self, enc: &Encoding<G>) -> f32 {
         for e in &mut self.entries {
             e.total_value = 0.0;
             e.total_visit_prob = 0.0;
             normalize_to(&e.total_sigma, &mut e.tmp);
         }
-        self.visit2(enc, enc.root, [1.0, 1.0], 1.0);
+        self.visit2(enc, enc.root, [1.0, 1.0], 1.0)
+    }
+
+    fn get_strategy(&mut self, enc: &Encoding<G>) -> HashMap<G::Infoset, InfosetStrategy<G::Action>> {
+        self.average_value(enc);
         self.entries.iter().enumerate().map(|(i, e)| {
             let k = enc.infosets[i].orig.clone();
             let v = InfosetStrategy {
@@ -177,17 +312,66 @@ impl Cfr {
         }).collect()
     }
 
+    fn average_strategy(&mut self, _enc: &Encoding<G>) -> Vec<Vec<f32>> {
+        self.entries.iter().map(|e| {
+            let mut sigma = vec![0.0; e.total_sigma.len()];
+            normalize_to(&e.total_sigma, &mut sigma);
+            sigma
+        }).collect()
+    }
+}
+
+impl Cfr {
+    pub fn new<G: Game>(enc: &Encoding<G>) -> Self {
+        Cfr::with_variant(enc, Variant::Vanilla)
+    }
+
+    pub fn with_variant<G: Game>(enc: &Encoding<G>, variant: Variant) -> Self {
+        Cfr {
+            entries: enc.infosets.iter().map(|i| CfrEntry::new(i.actions.len())).collect(),
+            variant,
+            alternating: variant != Variant::Vanilla,
+            iteration: 0,
+        }
+    }
+
+    // Applied to the entries of the updated player(s) after each update.
+    fn discount<G: Game>(&mut self, enc: &Encoding<G>, player: Option<usize>) {
+        let t = self.iteration as f32;
+        let (pos, neg, avg) = match self.variant {
+            Variant::Vanilla => return,
+            Variant::CfrPlus => (1.0, 0.0, t / (t + 1.0)),
+            Variant::Linear => (t / (t + 1.0), t / (t + 1.0), t / (t + 1.0)),
+            Variant::Discounted { alpha, beta, gamma } => {
+                let a = t.powf(alpha);
+                let b = t.powf(beta);
+                (a / (a + 1.0), b / (b + 1.0), (t / (t + 1.0)).powf(gamma))
+            }
+        };
+        for (e, inf) in self.entries.iter_mut().zip(&enc.infosets) {
+            if player.is_some_and(|p| p != inf.player) {
+                continue;
+            }
+            for r in &mut e.total_regret {
+                *r *= if *r > 0.0 { pos } else { neg };
+            }
+            for s in &mut e.total_sigma {
+                *s *= avg;
+            }
+        }
+    }
+
     fn visit2<G: Game>(&mut self, enc: &Encoding<G>, node: CompactNode, pi: [f32; 2], pi_chance: f32) -> f32 {
-        match &enc.nodes[node] {
-            NodeInfo::Terminal(x) => *x,
-            NodeInfo::Chance(actions) => {
+        match enc.node(node) {
+            NodeRef::Terminal(x) => x,
+            NodeRef::Chance { probs, children } => {
                 let mut s = 0.0;
-                for &(prob, next_node) in actions {
+                for (&prob, &next_node) in probs.iter().zip(children) {
                     s += prob * self.visit2(enc, next_node, pi, pi_chance * prob);
                 }
                 s
             }
-            &NodeInfo::Choice { player, infoset, ref actions } => {
+            NodeRef::Choice { player, infoset, children: actions } => {
                 let mut s = 0.0;
                 for i in 0..actions.len() {
                     let mut pp = pi;
@@ -209,20 +393,22 @@ impl Cfr {
         }
     }
 
-    fn visit<G: Game>(&mut self, enc: &Encoding<G>, node: CompactNode, pi: [f32; 2], pi_chance: f32) -> f32 {
+    // Only the regrets and average strategy of the given player are updated
+    // (of both if None).
+    fn visit<G: Game>(&mut self, enc: &Encoding<G>, node: CompactNode, pi: [f32; 2], pi_chance: f32, update: Option<usize>) -> f32 {
         if pi[0] < 1e-7 && pi[1] < 1e-7 {
             return 0.0;
         }
-        match &enc.nodes[node] {
-            NodeInfo::Terminal(x) => *x,
-            NodeInfo::Chance(actions) => {
+        match enc.node(node) {
+            NodeRef::Terminal(x) => x,
+            NodeRef::Chance { probs, children } => {
                 let mut s = 0.0;
-                for &(prob, next_node) in actions {
-                    s += prob * self.visit(enc, next_node, pi, pi_chance * prob);
+                for (&prob, &next_node) in probs.iter().zip(children) {
+                    s += prob * self.visit(enc, next_node, pi, pi_chance * prob, update);
                 }
                 s
             }
-            &NodeInfo::Choice { player, infoset, ref actions } => {
+            NodeRef::Choice { player, infoset, children: actions } => {
                 let num_actions = actions.len();
 
                 let mut s = 0.0;
@@ -230,10 +416,13 @@ impl Cfr {
                     let sigma = self.entries[infoset].cur_sigma[i];
                     let mut pp = pi;
                     pp[player] *= sigma;
-                    let ev = self.visit(enc, actions[i], pp, pi_chance);
+                    let ev = self.visit(enc, actions[i], pp, pi_chance, update);
                     s += sigma * ev;
                     self.entries[infoset].tmp[i] = ev;
                 }
+                if update.is_some_and(|p| p != player) {
+                    return s;
+                }
                 let entry = &mut self.entries[infoset];
 
                 let factor = pi_chance * pi[1 - player] * if player == 0 { 1.0 } else { -1.0 };
@@ -250,7 +439,322 @@ impl Cfr {
     }
 }
 
-fn normalize_to(xs: &[f32], dst: &mut [f32]) {
+// Same as Cfr, but each traversal is split into subtrees rooted
+// split_depth levels below the root (at chance and choice nodes alike),
+// which are traversed in parallel. Updates from each subtree are collected
+// separately and merged in a fixed order, so the results don't depend
+// on scheduling, although they can differ from Cfr in the last bits.
+#[cfg(feature = "heavy")]
+pub struct ParallelCfr {
+    pub cfr: Cfr,
+    pub split_depth: usize,
+}
+
+// Regret and average strategy updates by infoset.
+#[cfg(feature = "heavy")]
+type Updates = fnv::FnvHashMap<usize, (Vec<f32>, Vec<f32>)>;
+
+#[cfg(feature = "heavy")]
+impl ParallelCfr {
+    pub fn with_variant<G: Game>(enc: &Encoding<G>, variant: Variant) -> Self {
+        ParallelCfr {
+            cfr: Cfr::with_variant(enc, variant),
+            split_depth: 2,
+        }
+    }
+
+    fn traverse<G>(&mut self, enc: &Encoding<G>, update: Option<usize>)
+    where G: Game, G::Action: Send + Sync, G::Infoset: Send + Sync {
+        use rayon::prelude::*;
+        let mut frontier = Vec::new();
+        self.collect_frontier(enc, enc.root, [1.0, 1.0], 1.0, 0, &mut frontier);
+        let cfr = &self.cfr;
+        let results: Vec<(f32, Updates)> = frontier.par_iter().map(|&(node, pi, pi_chance)| {
+            let mut updates = Updates::default();
+            let v = cfr.visit_subtree(enc, node, pi, pi_chance, update, &mut updates);
+            (v, updates)
+        }).collect();
+        for (_, updates) in &results {
+            for (&infoset, (regret, sigma)) in updates {
+                let e = &mut self.cfr.entries[infoset];
+                for (x, dx) in e.total_regret.iter_mut().zip(regret) {
+                    *x += dx;
+                }
+                for (x, dx) in e.total_sigma.iter_mut().zip(sigma) {
+                    *x += dx;
+                }
+            }
+        }
+        let mut values = results.into_iter().map(|(v, _)| v);
+        self.visit_top(enc, enc.root, [1.0, 1.0], 1.0, 0, update, &mut values);
+        assert!(values.next().is_none());
+    }
+
+    fn collect_frontier<G: Game>(
+        &self, enc: &Encoding<G>, node: CompactNode, pi: [f32; 2], pi_chance: f32, depth: usize,
+        frontier: &mut Vec<(CompactNode, [f32; 2], f32)>,
+    ) {
+        if pi[0] < 1e-7 && pi[1] < 1e-7 {
+            return;
+        }
+        if depth == self.split_depth {
+            frontier.push((node, pi, pi_chance));
+            return;
+        }
+        match enc.node(node) {
+            NodeRef::Terminal(_) => {}
+            NodeRef::Chance { probs, children } => {
+                for (&prob, &next_node) in probs.iter().zip(children) {
+                    self.collect_frontier(enc, next_node, pi, pi_chance * prob, depth + 1, frontier);
+                }
+            }
+            NodeRef::Choice { player, infoset, children: actions } => {
+                for (i, &next_node) in actions.iter().enumerate() {
+                    let mut pp = pi;
+                    pp[player] *= self.cfr.entries[infoset].cur_sigma[i];
+                    self.collect_frontier(enc, next_node, pp, pi_chance, depth + 1, frontier);
+                }
+            }
+        }
+    }
+
+    // Cfr::visit() above the frontier, taking subtree values from `values`
+    // in the order collect_frontier() produced them.
+    #[allow(clippy::too_many_arguments)]
+    fn visit_top<G: Game>(
+        &mut self, enc: &Encoding<G>, node: CompactNode, pi: [f32; 2], pi_chance: f32, depth: usize,
+        update: Option<usize>, values: &mut impl Iterator<Item=f32>,
+    ) -> f32 {
+        if pi[0] < 1e-7 && pi[1] < 1e-7 {
+            return 0.0;
+        }
+        if depth == self.split_depth {
+            return values.next().unwrap();
+        }
+        match enc.node(node) {
+            NodeRef::Terminal(x) => x,
+            NodeRef::Chance { probs, children } => {
+                let mut s = 0.0;
+                for (&prob, &next_node) in probs.iter().zip(children) {
+                    s += prob * self.visit_top(enc, next_node, pi, pi_chance * prob, depth + 1, update, values);
+                }
+                s
+            }
+            NodeRef::Choice { player, infoset, children: actions } => {
+                let mut s = 0.0;
+                for (i, &next_node) in actions.iter().enumerate() {
+                    let sigma = self.cfr.entries[infoset].cur_sigma[i];
+                    let mut pp = pi;
+                    pp[player] *= sigma;
+                    let ev = self.visit_top(enc, next_node, pp, pi_chance, depth + 1, update, values);
+                    s += sigma * ev;
+                    self.cfr.entries[infoset].tmp[i] = ev;
+                }
+                if update.is_some_and(|p| p != player) {
+                    return s;
+                }
+                let entry = &mut self.cfr.entries[infoset];
+                let factor = pi_chance * pi[1 - player] * if player == 0 { 1.0 } else { -1.0 };
+                for i in 0..actions.len() {
+                    entry.total_regret[i] += factor * (entry.tmp[i] - s);
+                    entry.total_sigma[i] += pi[player] * entry.cur_sigma[i];
+                }
+                s
+            }
+        }
+    }
+}
+
+#[cfg(feature = "heavy")]
+impl<G> Solver<G> for ParallelCfr
+where G: Game, G::Action: Send + Sync, G::Infoset: Send + Sync {
+    fn step(&mut self, enc: &Encoding<G>) {
+        self.cfr.iteration += 1;
+        if self.cfr.alternating {
+            for player in 0..2 {
+                self.cfr.compute_cur_sigma();
+                self.traverse(enc, Some(player));
+                self.cfr.discount(enc, Some(player));
+            }
+        } else {
+            self.cfr.compute_cur_sigma();
+            self.traverse(enc, None);
+            self.cfr.discount(enc, None);
+        }
+    }
+
+    fn average_value(&mut self, enc: &Encoding<G>) -> f32 {
+        self.cfr.average_value(enc)
+    }
+
+    fn get_strategy(&mut self, enc: &Encoding<G>) -> HashMap<G::Infoset, InfosetStrategy<G::Action>> {
+        self.cfr.get_strategy(enc)
+    }
+
+    fn average_strategy(&mut self, enc: &Encoding<G>) -> Vec<Vec<f32>> {
+        Solver::<G>::average_strategy(&mut self.cfr, enc)
+    }
+}
+
+#[cfg(feature = "heavy")]
+impl Cfr {
+    // Like visit(), but only reads self, putting the updates aside.
+    fn visit_subtree<G: Game>(
+        &self, enc: &Encoding<G>, node: CompactNode, pi: [f32; 2], pi_chance: f32,
+        update: Option<usize>, updates: &mut Updates,
+    ) -> f32 {
+        if pi[0] < 1e-7 && pi[1] < 1e-7 {
+            return 0.0;
+        }
+        match enc.node(node) {
+            NodeRef::Terminal(x) => x,
+            NodeRef::Chance { probs, children } => {
+                let mut s = 0.0;
+                for (&prob, &next_node) in probs.iter().zip(children) {
+                    s += prob * self.visit_subtree(enc, next_node, pi, pi_chance * prob, update, updates);
+                }
+                s
+            }
+            NodeRef::Choice { player, infoset, children: actions } => {
+                let cur_sigma = &self.entries[infoset].cur_sigma;
+                let mut evs = Vec::with_capacity(actions.len());
+                let mut s = 0.0;
+                for (i, &next_node) in actions.iter().enumerate() {
+                    let mut pp = pi;
+                    pp[player] *= cur_sigma[i];
+                    let ev = self.visit_subtree(enc, next_node, pp, pi_chance, update, updates);
+                    s += cur_sigma[i] * ev;
+                    evs.push(ev);
+                }
+                if update.is_some_and(|p| p != player) {
+                    return s;
+                }
+                let (regret, sigma) = updates.entry(infoset)
+                    .or_insert_with(|| (vec![0.0; actions.len()], vec![0.0; actions.len()]));
+                let factor = pi_chance * pi[1 - player] * if player == 0 { 1.0 } else { -1.0 };
+                for i in 0..actions.len() {
+                    regret[i] += factor * (evs[i] - s);
+                    sigma[i] += pi[player] * cur_sigma[i];
+                }
+                s
+            }
+        }
+    }
+}
+
+// Value for `player` of the best response to the opponent playing `sigma`
+// (indexed like enc.infosets; entries for `player` are ignored).
+pub fn best_response_value<G: Game>(enc: &Encoding<G>, sigma: &[Vec<f32>], player: usize) -> f32 {
+    assert_eq!(sigma.len(), enc.infosets.len());
+    let mut nodes_by_infoset = vec![Vec::new(); enc.infosets.len()];
+    for i in 0..enc.num_nodes() as CompactNode {
+        if let NodeRef::Choice { infoset, .. } = enc.node(i) {
+            nodes_by_infoset[infoset].push(i);
+        }
+    }
+    let mut br = BestResponse {
+        enc,
+        sigma,
+        player,
+        nodes_by_infoset,
+        reach: vec![0.0; enc.num_nodes()],
+        node_value: vec![None; enc.num_nodes()],
+        best_action: vec![None; enc.infosets.len()],
+    };
+    br.compute_reach(enc.root, 1.0);
+    br.value(enc.root)
+}
+
+// How much both players together could gain by deviating from sigma.
+// Zero for a Nash equilibrium.
+pub fn nash_conv<G: Game>(enc: &Encoding<G>, sigma: &[Vec<f32>]) -> f32 {
+    best_response_value(enc, sigma, 0) + best_response_value(enc, sigma, 1)
+}
+
+struct BestResponse<'a, G: Game> {
+    enc: &'a Encoding<G>,
+    sigma: &'a [Vec<f32>],
+    player: usize,
+    nodes_by_infoset: Vec<Vec<CompactNode>>,
+    reach: Vec<f32>,  // of the opponent and chance
+    node_value: Vec<Option<f32>>,
+    best_action: Vec<Option<usize>>,
+}
+
+impl<'a, G: Game> BestResponse<'a, G> {
+    fn compute_reach(&mut self, node: CompactNode, reach: f32) {
+        self.reach[node as usize] = reach;
+        match self.enc.node(node) {
+            NodeRef::Terminal(_) => {}
+            NodeRef::Chance { probs, children } => {
+                for (&prob, &next_node) in probs.iter().zip(children) {
+                    self.compute_reach(next_node, reach * prob);
+                }
+            }
+            NodeRef::Choice { player, infoset, children: actions } => {
+                for (i, &next_node) in actions.iter().enumerate() {
+                    let p = if player == self.player { 1.0 } else { self.sigma[infoset][i] };
+                    self.compute_reach(next_node, reach * p);
+                }
+            }
+        }
+    }
+
+    fn value(&mut self, node: CompactNode) -> f32 {
+        if let Some(v) = self.node_value[node as usize] {
+            return v;
+        }
+        let v = match self.enc.node(node) {
+            NodeRef::Terminal(x) => if self.player == 0 { x } else { -x },
+            NodeRef::Chance { probs, children } => {
+                probs.iter().zip(children).map(|(&prob, &next_node)| prob * self.value(next_node)).sum()
+            }
+            NodeRef::Choice { player, infoset, children: actions } => {
+                if player == self.player {
+                    let a = self.best_action(infoset);
+                    self.value(actions[a])
+                } else {
+                    actions.iter().enumerate()
+                        .map(|(i, &next_node)| self.sigma[infoset][i] * self.value(next_node))
+                        .sum()
+                }
+            }
+        };
+        self.node_value[node as usize] = Some(v);
+        v
+    }
+
+    // Thanks to perfect recall, the choices in the subtrees only depend
+    // on the infosets below, so there are no cycles.
+    fn best_action(&mut self, infoset: usize) -> usize {
+        if let Some(a) = self.best_action[infoset] {
+            return a;
+        }
+        let num_actions = self.enc.infosets[infoset].actions.len();
+        let mut cf_values = vec![0.0; num_actions];
+        for j in 0..self.nodes_by_infoset[infoset].len() {
+            let node = self.nodes_by_infoset[infoset][j];
+            let reach = self.reach[node as usize];
+            let next_nodes = match self.enc.node(node) {
+                NodeRef::Choice { children, .. } => children,
+                _ => unreachable!(),
+            };
+            for (v, &next_node) in cf_values.iter_mut().zip(next_nodes) {
+                *v += reach * self.value(next_node);
+            }
+        }
+        let mut best = 0;
+        for i in 1..num_actions {
+            if cf_values[i] > cf_values[best] {
+                best = i;
+            }
+        }
+        self.best_action[infoset] = Some(best);
+        best
+    }
+}
+
+pub(crate) fn normalize_to(xs: &[f32], dst: &mut [f32]) {
     let s: f32 = xs.iter().map(|x| x.max(0.0)).sum();
     assert_eq!(xs.len(), dst.len());
     if s == 0.0 {
@@ -0,0 +1,360 @@
+// Small games with published equilibrium values,
+// to catch regressions in the solvers.
+//
+// Values are for player 0, like everywhere in cfr.rs.
+
+use crate::cfr::{Game, NodeInfo, NodeRef, Encoding, CompactNode, Cfr, Solver, Variant, nash_conv};
+use crate::mccfr::{Mccfr, Sampling};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PokerAction {
+    Deal(u8),
+    Fold,
+    Call,  // or check
+    Raise,  // or bet
+}
+
+// Kuhn poker: three cards, one betting round with a single bet size.
+struct Kuhn;
+
+#[derive(Clone)]
+struct KuhnState {
+    cards: Vec<u8>,
+    bets: Vec<PokerAction>,
+}
+
+impl Game for Kuhn {
+    type Action = PokerAction;
+    type Infoset = (u8, Vec<PokerAction>);
+    type State = KuhnState;
+
+    fn initial_state(&mut self) -> KuhnState {
+        KuhnState { cards: Vec::new(), bets: Vec::new() }
+    }
+
+    fn node_info(&mut self, s: &KuhnState) -> NodeInfo<PokerAction, Self::Infoset> {
+        use PokerAction::*;
+        if s.cards.len() < 2 {
+            let remaining: Vec<u8> = (0..3).filter(|c| !s.cards.contains(c)).collect();
+            let p = 1.0 / remaining.len() as f32;
+            return NodeInfo::Chance(remaining.into_iter().map(|c| (p, Deal(c))).collect());
+        }
+        let showdown = if s.cards[0] > s.cards[1] { 1.0 } else { -1.0 };
+        match s.bets[..] {
+            [Call, Call] => NodeInfo::Terminal(showdown),
+            [Raise, Call] | [Call, Raise, Call] => NodeInfo::Terminal(2.0 * showdown),
+            [Raise, Fold] => NodeInfo::Terminal(1.0),
+            [Call, Raise, Fold] => NodeInfo::Terminal(-1.0),
+            _ => {
+                let player = s.bets.len() % 2;
+                let facing_bet = s.bets.last() == Some(&Raise);
+                NodeInfo::Choice {
+                    player,
+                    infoset: (s.cards[player], s.bets.clone()),
+                    actions: if facing_bet { vec![Fold, Call] } else { vec![Call, Raise] },
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, s: &mut KuhnState, a: &PokerAction) {
+        match *a {
+            PokerAction::Deal(c) => s.cards.push(c),
+            a => s.bets.push(a),
+        }
+    }
+}
+
+// Leduc hold'em: six cards (two suits of three ranks), private card,
+// betting round with bet size 2, public card, betting round with bet size 4.
+// At most two raises per round. Pair with the public card wins,
+// otherwise the higher private card.
+struct Leduc;
+
+#[derive(Clone)]
+struct LeducState {
+    cards: Vec<u8>,  // rank is card / 2
+    bets: Vec<PokerAction>,
+    round: usize,
+    round_len: usize,
+    raises: usize,
+    contrib: [i32; 2],
+    folded: Option<usize>,
+}
+
+impl Game for Leduc {
+    type Action = PokerAction;
+    // private rank, public rank, betting
+    type Infoset = (u8, Option<u8>, Vec<PokerAction>);
+    type State = LeducState;
+
+    fn initial_state(&mut self) -> LeducState {
+        LeducState {
+            cards: Vec::new(),
+            bets: Vec::new(),
+            round: 0,
+            round_len: 0,
+            raises: 0,
+            contrib: [1, 1],
+            folded: None,
+        }
+    }
+
+    fn node_info(&mut self, s: &LeducState) -> NodeInfo<PokerAction, Self::Infoset> {
+        use PokerAction::*;
+        if let Some(p) = s.folded {
+            return NodeInfo::Terminal(if p == 0 { -s.contrib[0] } else { s.contrib[1] } as f32);
+        }
+        if s.cards.len() < 2 || s.round == 1 && s.cards.len() < 3 {
+            let remaining: Vec<u8> = (0..6).filter(|c| !s.cards.contains(c)).collect();
+            let p = 1.0 / remaining.len() as f32;
+            return NodeInfo::Chance(remaining.into_iter().map(|c| (p, Deal(c))).collect());
+        }
+        if s.round == 2 {
+            let public = s.cards[2] / 2;
+            let strength = |c: u8| if c / 2 == public { 10 } else { c / 2 };
+            let (s0, s1) = (strength(s.cards[0]), strength(s.cards[1]));
+            return NodeInfo::Terminal(if s0 > s1 {
+                s.contrib[0] as f32
+            } else if s0 < s1 {
+                -s.contrib[0] as f32
+            } else {
+                0.0
+            });
+        }
+        let player = s.round_len % 2;
+        let mut actions = Vec::new();
+        if s.contrib[player] < s.contrib[1 - player] {
+            actions.push(Fold);
+        }
+        actions.push(Call);
+        if s.raises < 2 {
+            actions.push(Raise);
+        }
+        NodeInfo::Choice {
+            player,
+            infoset: (s.cards[player] / 2, s.cards.get(2).map(|c| c / 2), s.bets.clone()),
+            actions,
+        }
+    }
+
+    fn apply(&mut self, s: &mut LeducState, a: &PokerAction) {
+        let player = s.round_len % 2;
+        match *a {
+            PokerAction::Deal(c) => {
+                s.cards.push(c);
+                return;
+            }
+            PokerAction::Fold => s.folded = Some(player),
+            PokerAction::Call => {
+                let round_over = s.contrib[player] < s.contrib[1 - player] || s.round_len > 0;
+                s.contrib[player] = s.contrib[1 - player];
+                if round_over {
+                    s.round += 1;
+                    s.round_len = 0;
+                    s.raises = 0;
+                    s.bets.push(*a);
+                    return;
+                }
+            }
+            PokerAction::Raise => {
+                s.contrib[player] = s.contrib[1 - player] + if s.round == 0 { 2 } else { 4 };
+                s.raises += 1;
+            }
+        }
+        s.round_len += 1;
+        s.bets.push(*a);
+    }
+}
+
+// Liar's dice with one six-sided die per player. Bids are
+// (quantity, face), each higher than the previous one in the order
+// (1, 1), ..., (1, 6), (2, 1), ..., (2, 6). Sixes are wild.
+// Calling liar ends the game, whoever was wrong loses 1.
+struct LiarsDice;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum DiceAction {
+    Roll(u8),
+    Bid(u8),  // quantity is bid / 6 + 1, face is bid % 6 + 1
+    Liar,
+}
+
+#[derive(Clone)]
+struct DiceState {
+    dice: Vec<u8>,
+    bids: Vec<DiceAction>,
+}
+
+impl Game for LiarsDice {
+    type Action = DiceAction;
+    type Infoset = (u8, Vec<DiceAction>);
+    type State = DiceState;
+
+    fn initial_state(&mut self) -> DiceState {
+        DiceState { dice: Vec::new(), bids: Vec::new() }
+    }
+
+    fn node_info(&mut self, s: &DiceState) -> NodeInfo<DiceAction, Self::Infoset> {
+        use DiceAction::*;
+        if s.dice.len() < 2 {
+            return NodeInfo::Chance((1..=6).map(|d| (1.0 / 6.0, Roll(d))).collect());
+        }
+        let player = s.bids.len() % 2;
+        let last_bid = s.bids.iter().rev().find_map(|&a| match a {
+            Bid(b) => Some(b),
+            _ => None,
+        });
+        if s.bids.last() == Some(&Liar) {
+            let b = last_bid.unwrap();
+            let face = b % 6 + 1;
+            let count = s.dice.iter().filter(|&&d| d == face || d == 6).count() as u8;
+            // `player` would be next to act, so the other one called
+            let caller = 1 - player;
+            let caller_wins = count < b / 6 + 1;
+            let caller_value = if caller_wins { 1.0 } else { -1.0 };
+            return NodeInfo::Terminal(if caller == 0 { caller_value } else { -caller_value });
+        }
+        let first_bid = last_bid.map_or(0, |b| b + 1);
+        let mut actions: Vec<DiceAction> = (first_bid..12).map(Bid).collect();
+        if last_bid.is_some() {
+            actions.push(Liar);
+        }
+        NodeInfo::Choice {
+            player,
+            infoset: (s.dice[player], s.bids.clone()),
+            actions,
+        }
+    }
+
+    fn apply(&mut self, s: &mut DiceState, a: &DiceAction) {
+        match *a {
+            DiceAction::Roll(d) => s.dice.push(d),
+            a => s.bids.push(a),
+        }
+    }
+}
+
+// Solves with each variant, checks that the value and nash conv
+// are within the given bounds.
+fn check<G: Game>(enc: &Encoding<G>, steps: usize, expected_value: f32, bounds: &[(Variant, f32, f32)]) {
+    for &(variant, value_tol, max_nash_conv) in bounds {
+        let mut cfr = Cfr::with_variant(enc, variant);
+        check_solver(enc, &mut cfr, steps, expected_value, value_tol, max_nash_conv, &format!("{:?}", variant));
+    }
+}
+
+// Same with cfr::ParallelCfr.
+#[cfg(feature = "heavy")]
+fn check_parallel<G>(enc: &Encoding<G>, steps: usize, expected_value: f32, bounds: &[(Variant, f32, f32)])
+where G: Game, G::Action: Send + Sync, G::Infoset: Send + Sync {
+    for &(variant, value_tol, max_nash_conv) in bounds {
+        let mut cfr = crate::cfr::ParallelCfr::with_variant(enc, variant);
+        check_solver(enc, &mut cfr, steps, expected_value, value_tol, max_nash_conv, &format!("parallel {:?}", variant));
+    }
+}
+
+fn check_solver<G: Game>(
+    enc: &Encoding<G>, solver: &mut dyn Solver<G>, steps: usize,
+    expected_value: f32, value_tol: f32, max_nash_conv: f32, name: &str,
+) {
+    for _ in 0..steps {
+        solver.step(enc);
+    }
+    let value = solver.average_value(enc);
+    let nc = nash_conv(enc, &solver.average_strategy(enc));
+    assert!((value - expected_value).abs() < value_tol, "{} value {}", name, value);
+    assert!(nc >= -1e-5 && nc < max_nash_conv, "{} nash conv {}", name, nc);
+}
+
+// Same with mccfr::Mccfr, which has no average value of its own,
+// so the value is that of the average strategy.
+fn check_sampling<G: Game>(
+    g: &mut G, enc: &Encoding<G>, steps: usize, expected_value: f32, bounds: &[(Sampling, f32, f32)],
+) {
+    for &(sampling, value_tol, max_nash_conv) in bounds {
+        let mut mccfr = Mccfr::new(sampling, 42);
+        for _ in 0..steps {
+            mccfr.step(g);
+        }
+        let sigma = mccfr.average_strategy(enc);
+        let value = strategy_value(enc, &sigma, enc.root);
+        let nc = nash_conv(enc, &sigma);
+        assert!((value - expected_value).abs() < value_tol, "{:?} value {}", sampling, value);
+        assert!(nc >= -1e-5 && nc < max_nash_conv, "{:?} nash conv {}", sampling, nc);
+    }
+}
+
+// Expected value of the subtree when both players follow sigma.
+fn strategy_value<G: Game>(enc: &Encoding<G>, sigma: &[Vec<f32>], node: CompactNode) -> f32 {
+    match enc.node(node) {
+        NodeRef::Terminal(x) => x,
+        NodeRef::Chance { probs, children } => probs.iter().zip(children)
+            .map(|(&p, &child)| p * strategy_value(enc, sigma, child))
+            .sum(),
+        NodeRef::Choice { infoset, children, .. } => sigma[infoset].iter().zip(children)
+            .map(|(&p, &child)| p * strategy_value(enc, sigma, child))
+            .sum(),
+    }
+}
+
+#[test]
+fn test_kuhn() {
+    let mut enc = Encoding::new(&mut Kuhn);
+    assert_eq!(enc.infosets.len(), 12);
+    // solvers don't need it
+    let size = enc.heap_size();
+    enc.strip_debug_info();
+    assert!(enc.heap_size() < size);
+    // Kuhn 1950
+    check(&enc, 10_000, -1.0 / 18.0, &[
+        (Variant::Vanilla, 1e-4, 1e-2),
+        (Variant::CfrPlus, 1e-5, 1e-4),
+        (Variant::Linear, 1e-5, 1e-4),
+        (Variant::DCFR, 1e-5, 1e-4),
+    ]);
+    #[cfg(feature = "heavy")]
+    check_parallel(&enc, 10_000, -1.0 / 18.0, &[
+        (Variant::Linear, 1e-5, 1e-4),
+    ]);
+    check_sampling(&mut Kuhn, &enc, 100_000, -1.0 / 18.0, &[
+        (Sampling::External, 5e-3, 0.02),
+        (Sampling::Outcome { exploration: 0.6 }, 5e-3, 0.05),
+    ]);
+}
+
+#[test]
+fn test_leduc() {
+    let enc = Encoding::new(&mut Leduc);
+    // suits don't matter, so infosets only have ranks
+    assert_eq!(enc.infosets.len(), 288);
+    assert_eq!(enc.num_nodes(), 9457);
+    // -0.0856 (OpenSpiel)
+    check(&enc, 1000, -0.085606, &[
+        (Variant::Vanilla, 1e-2, 0.1),
+        (Variant::CfrPlus, 1e-4, 1e-3),
+        (Variant::Linear, 1e-3, 0.02),
+        (Variant::DCFR, 1e-4, 1e-3),
+    ]);
+    #[cfg(feature = "heavy")]
+    check_parallel(&enc, 1000, -0.085606, &[
+        (Variant::DCFR, 1e-4, 1e-3),
+    ]);
+    check_sampling(&mut Leduc, &enc, 100_000, -0.085606, &[
+        (Sampling::External, 1e-2, 0.15),
+        (Sampling::Outcome { exploration: 0.6 }, 0.05, 0.8),
+    ]);
+}
+
+#[test]
+fn test_liars_dice() {
+    let enc = Encoding::new(&mut LiarsDice);
+    assert_eq!(enc.infosets.len(), 24576);
+    // -7/258 (OpenSpiel, liars_dice with one die per player)
+    check(&enc, 100, -7.0 / 258.0, &[
+        (Variant::Vanilla, 1e-2, 0.3),
+        (Variant::CfrPlus, 2e-3, 0.02),
+        (Variant::Linear, 1e-3, 0.03),
+        (Variant::DCFR, 2e-3, 1e-2),
+    ]);
+}
@@ -0,0 +1,547 @@
+// On-disk format for CFR encodings and solved strategies,
+// so that expensive solves (like bin/opening.rs) can be done offline
+// and looked up by players at game time.
+//
+// Little-endian binary, indices and lengths as u32.
+// Each file starts with a magic string and FORMAT_VERSION;
+// files written by a different version are rejected.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use crate::cfr::{Game, Encoding, CompactInfoset, NodeRecord, TERMINAL, CHANCE};
+use crate::game::{Color, Square, Move, Piece, PieceKind};
+use crate::rbc_xf::{Action, Inflet};
+
+// Bump on any change to the layout, including Codec impls of game types.
+pub const FORMAT_VERSION: u32 = 2;
+
+const ENCODING_MAGIC: &[u8; 4] = b"CFRE";
+const STRATEGY_MAGIC: &[u8; 4] = b"CFRS";
+
+pub trait Codec: Sized {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()>;
+    fn read_from(r: &mut impl Read) -> io::Result<Self>;
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_header(w: &mut impl Write, magic: &[u8; 4]) -> io::Result<()> {
+    w.write_all(magic)?;
+    FORMAT_VERSION.write_to(w)
+}
+
+fn read_header(r: &mut impl Read, magic: &[u8; 4]) -> io::Result<()> {
+    let mut m = [0u8; 4];
+    r.read_exact(&mut m)?;
+    if &m != magic {
+        return Err(invalid_data(format!("bad magic {:?}, expected {:?}", m, magic)));
+    }
+    let version = u32::read_from(r)?;
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!("format version {}, expected {}", version, FORMAT_VERSION)));
+    }
+    Ok(())
+}
+
+impl Codec for u8 {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&[*self])
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let mut buf = [0u8; 1];
+        r.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+}
+
+impl Codec for u32 {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let mut buf = [0u8; 4];
+        r.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+}
+
+impl Codec for usize {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        u32::try_from(*self).map_err(|_| invalid_data(format!("{} does not fit in u32", self)))?.write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        Ok(u32::read_from(r)? as usize)
+    }
+}
+
+impl Codec for f32 {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        self.to_bits().write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        Ok(f32::from_bits(u32::read_from(r)?))
+    }
+}
+
+impl<T: Codec> Codec for Vec<T> {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        self.len().write_to(w)?;
+        for x in self {
+            x.write_to(w)?;
+        }
+        Ok(())
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let n = usize::read_from(r)?;
+        // don't trust n for preallocation, the file could be truncated
+        let mut result = Vec::with_capacity(n.min(1 << 16));
+        for _ in 0..n {
+            result.push(T::read_from(r)?);
+        }
+        Ok(result)
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            None => 0u8.write_to(w),
+            Some(x) => {
+                1u8.write_to(w)?;
+                x.write_to(w)
+            }
+        }
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::read_from(r)?)),
+            t => Err(invalid_data(format!("bad Option tag {}", t))),
+        }
+    }
+}
+
+impl<A: Codec, B: Codec> Codec for (A, B) {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        self.0.write_to(w)?;
+        self.1.write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let a = A::read_from(r)?;
+        let b = B::read_from(r)?;
+        Ok((a, b))
+    }
+}
+
+impl Codec for Square {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        (self.0 as u8).write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            x @ 0..=63 => Ok(Square(x as i8)),
+            x => Err(invalid_data(format!("bad square {}", x))),
+        }
+    }
+}
+
+impl Codec for Color {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        (*self as u8).write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            0 => Ok(Color::White),
+            1 => Ok(Color::Black),
+            x => Err(invalid_data(format!("bad color {}", x))),
+        }
+    }
+}
+
+impl Codec for PieceKind {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        (self.to_int() as u8).write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            x @ 0..=5 => Ok(PieceKind::from_int(x as u32)),
+            x => Err(invalid_data(format!("bad piece kind {}", x))),
+        }
+    }
+}
+
+impl Codec for Piece {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        self.kind.write_to(w)?;
+        self.color.write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let kind = PieceKind::read_from(r)?;
+        let color = Color::read_from(r)?;
+        Ok(Piece { kind, color })
+    }
+}
+
+impl Codec for Move {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        self.from.write_to(w)?;
+        self.to.write_to(w)?;
+        self.promotion.write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let from = Square::read_from(r)?;
+        let to = Square::read_from(r)?;
+        let promotion = Option::read_from(r)?;
+        Ok(Move { from, to, promotion })
+    }
+}
+
+impl Codec for Action {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Action::ChoosePosition(i) => {
+                0u8.write_to(w)?;
+                i.write_to(w)
+            }
+            Action::Sense(sq) => {
+                1u8.write_to(w)?;
+                sq.write_to(w)
+            }
+            Action::Move(m) => {
+                2u8.write_to(w)?;
+                m.write_to(w)
+            }
+        }
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            0 => Ok(Action::ChoosePosition(usize::read_from(r)?)),
+            1 => Ok(Action::Sense(Square::read_from(r)?)),
+            2 => Ok(Action::Move(Option::read_from(r)?)),
+            t => Err(invalid_data(format!("bad Action tag {}", t))),
+        }
+    }
+}
+
+impl Codec for Inflet {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Inflet::MyColor(c) => {
+                0u8.write_to(w)?;
+                c.write_to(w)
+            }
+            Inflet::ChoosePosition(i) => {
+                1u8.write_to(w)?;
+                i.write_to(w)
+            }
+            Inflet::Sense(sq, result) => {
+                2u8.write_to(w)?;
+                sq.write_to(w)?;
+                result.write_to(w)
+            }
+            Inflet::OpponentCapture(sq) => {
+                3u8.write_to(w)?;
+                sq.write_to(w)
+            }
+            Inflet::Move { requested, taken, capture } => {
+                4u8.write_to(w)?;
+                requested.write_to(w)?;
+                taken.write_to(w)?;
+                capture.write_to(w)
+            }
+        }
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        match u8::read_from(r)? {
+            0 => Ok(Inflet::MyColor(Color::read_from(r)?)),
+            1 => Ok(Inflet::ChoosePosition(usize::read_from(r)?)),
+            2 => {
+                let sq = Square::read_from(r)?;
+                let result = Vec::read_from(r)?;
+                Ok(Inflet::Sense(sq, result))
+            }
+            3 => Ok(Inflet::OpponentCapture(Option::read_from(r)?)),
+            4 => {
+                let requested = Option::read_from(r)?;
+                let taken = Option::read_from(r)?;
+                let capture = Option::read_from(r)?;
+                Ok(Inflet::Move { requested, taken, capture })
+            }
+            t => Err(invalid_data(format!("bad Inflet tag {}", t))),
+        }
+    }
+}
+
+impl Codec for NodeRecord {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        self.kind.write_to(w)?;
+        self.payload.write_to(w)?;
+        self.children_start.write_to(w)?;
+        self.num_children.write_to(w)
+    }
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let kind = u32::read_from(r)?;
+        let payload = u32::read_from(r)?;
+        let children_start = u32::read_from(r)?;
+        let num_children = u32::read_from(r)?;
+        Ok(NodeRecord { kind, payload, children_start, num_children })
+    }
+}
+
+// So that Encoding::node() can't go out of bounds on a corrupted file,
+// and neither can solvers indexing by infoset actions and parents.
+fn check_nodes<A, I>(
+    nodes: &[NodeRecord], children: &[u32], probs: &[f32],
+    infosets: &[CompactInfoset<A, I>], parents: &[Option<(u32, A)>],
+) -> io::Result<()> {
+    for (i, r) in nodes.iter().enumerate() {
+        let end = r.children_start as usize + r.num_children as usize;
+        let ok = end <= children.len() && match r.kind {
+            0 | 1 => infosets.get(r.payload as usize).is_some_and(|inf|
+                inf.player == r.kind as usize && inf.actions.len() == r.num_children as usize),
+            TERMINAL => r.num_children == 0,
+            CHANCE => r.payload as usize + r.num_children as usize <= probs.len(),
+            _ => false,
+        };
+        if !ok {
+            return Err(invalid_data(format!("bad node {} {:?}", i, r)));
+        }
+    }
+    if let Some(c) = children.iter().find(|&&c| c as usize >= nodes.len()) {
+        return Err(invalid_data(format!("bad child {}", c)));
+    }
+    // none at all after Encoding::strip_debug_info()
+    if !parents.is_empty() && parents.len() != nodes.len() {
+        return Err(invalid_data(format!("{} parents for {} nodes", parents.len(), nodes.len())));
+    }
+    if let Some((p, _)) = parents.iter().flatten().find(|(p, _)| *p as usize >= nodes.len()) {
+        return Err(invalid_data(format!("bad parent {}", p)));
+    }
+    Ok(())
+}
+
+pub fn write_encoding<G>(enc: &Encoding<G>, w: &mut impl Write) -> io::Result<()>
+where G: Game, G::Action: Codec, G::Infoset: Codec {
+    write_header(w, ENCODING_MAGIC)?;
+    enc.root.write_to(w)?;
+    enc.nodes.write_to(w)?;
+    enc.children.write_to(w)?;
+    enc.probs.write_to(w)?;
+    enc.infosets.len().write_to(w)?;
+    for inf in &enc.infosets {
+        inf.orig.write_to(w)?;
+        (inf.player as u8).write_to(w)?;
+        inf.actions.write_to(w)?;
+        inf.observable_history.write_to(w)?;
+    }
+    enc.parents.write_to(w)
+}
+
+pub fn read_encoding<G>(r: &mut impl Read) -> io::Result<Encoding<G>>
+where G: Game, G::Action: Codec, G::Infoset: Codec {
+    read_header(r, ENCODING_MAGIC)?;
+    let root = u32::read_from(r)?;
+    let nodes: Vec<NodeRecord> = Vec::read_from(r)?;
+    let children: Vec<u32> = Vec::read_from(r)?;
+    let probs: Vec<f32> = Vec::read_from(r)?;
+    let num_infosets = usize::read_from(r)?;
+    let mut infosets = Vec::with_capacity(num_infosets.min(1 << 16));
+    for _ in 0..num_infosets {
+        let orig = G::Infoset::read_from(r)?;
+        let player = u8::read_from(r)? as usize;
+        let actions = Vec::read_from(r)?;
+        let observable_history = Vec::read_from(r)?;
+        infosets.push(CompactInfoset { orig, player, actions, observable_history });
+    }
+    let parents = Vec::read_from(r)?;
+    check_nodes(&nodes, &children, &probs, &infosets, &parents)?;
+    if root as usize >= nodes.len() {
+        return Err(invalid_data(format!("bad root {}", root)));
+    }
+    let infoset_by_orig = infosets.iter().enumerate()
+        .map(|(i, inf)| (inf.orig.clone(), i))
+        .collect();
+    Ok(Encoding { infoset_by_orig, infosets, nodes, children, probs, parents, root })
+}
+
+pub fn save_encoding<G>(enc: &Encoding<G>, path: impl AsRef<Path>) -> io::Result<()>
+where G: Game, G::Action: Codec, G::Infoset: Codec {
+    let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+    write_encoding(enc, &mut w)?;
+    w.flush()
+}
+
+pub fn load_encoding<G>(path: impl AsRef<Path>) -> io::Result<Encoding<G>>
+where G: Game, G::Action: Codec, G::Infoset: Codec {
+    read_encoding(&mut io::BufReader::new(std::fs::File::open(path)?))
+}
+
+// Precomputed strategies for lookup at game time.
+pub struct StrategyBook<Action, Infoset: Eq + std::hash::Hash> {
+    strategies: HashMap<Infoset, Vec<(Action, f32)>>,
+}
+
+impl<Action, Infoset> StrategyBook<Action, Infoset>
+where Action: Codec + Clone, Infoset: Codec + Clone + Eq + std::hash::Hash {
+    // From Solver::average_strategy().
+    pub fn from_average_strategy<G>(enc: &Encoding<G>, sigma: &[Vec<f32>]) -> Self
+    where G: Game<Action=Action, Infoset=Infoset> {
+        assert_eq!(enc.infosets.len(), sigma.len());
+        let strategies = enc.infosets.iter().zip(sigma).map(|(inf, s)| {
+            assert_eq!(inf.actions.len(), s.len());
+            (inf.orig.clone(), inf.actions.iter().cloned().zip(s.iter().cloned()).collect())
+        }).collect();
+        StrategyBook { strategies }
+    }
+
+    // Action probabilities, or None if the infoset wasn't solved for.
+    pub fn get(&self, infoset: &Infoset) -> Option<&[(Action, f32)]> {
+        self.strategies.get(infoset).map(|s| s.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.strategies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strategies.is_empty()
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write_header(w, STRATEGY_MAGIC)?;
+        self.strategies.len().write_to(w)?;
+        // sorted by encoded infoset, so that the same book is the same file
+        let mut entries = Vec::with_capacity(self.strategies.len());
+        for (infoset, s) in &self.strategies {
+            let mut key = Vec::new();
+            infoset.write_to(&mut key)?;
+            entries.push((key, s));
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (key, s) in entries {
+            w.write_all(&key)?;
+            s.write_to(w)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        read_header(r, STRATEGY_MAGIC)?;
+        let n = usize::read_from(r)?;
+        let mut strategies = HashMap::with_capacity(n.min(1 << 16));
+        for _ in 0..n {
+            let infoset = Infoset::read_from(r)?;
+            let s = Vec::read_from(r)?;
+            strategies.insert(infoset, s);
+        }
+        Ok(StrategyBook { strategies })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        StrategyBook::read_from(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::{Cfr, Solver, Variant};
+    use crate::game::BoardState;
+    use crate::rbc_xf::{RbcGame, State, MaterialEvaluator, DEFAULT_INFO_PENALTY};
+
+    #[test]
+    fn test_roundtrip() {
+        let mut evaluator = MaterialEvaluator { info_penalty: DEFAULT_INFO_PENALTY };
+        let mut game = RbcGame::new(1 + 2, &mut evaluator, State::ChoosePositionBeforeSense(Color::Black), vec![
+            BoardState::initial(),
+            fen::BoardState::from_fen("rnbqkb1r/pppppppp/8/8/8/5n2/PPPPPPPP/RNBQKBNR w KQkq - 0 0").unwrap().into(),
+        ]);
+        let enc = Encoding::new(&mut game);
+        let mut buf = Vec::new();
+        write_encoding(&enc, &mut buf).unwrap();
+        let enc2: Encoding<RbcGame> = read_encoding(&mut buf.as_slice()).unwrap();
+        assert_eq!(enc.nodes, enc2.nodes);
+        assert_eq!(enc.children, enc2.children);
+        assert_eq!(enc.probs, enc2.probs);
+        assert_eq!(format!("{:?}", enc.infosets), format!("{:?}", enc2.infosets));
+        assert_eq!(format!("{:?}", enc.parents), format!("{:?}", enc2.parents));
+        assert_eq!(enc.infoset_by_orig, enc2.infoset_by_orig);
+        assert_eq!(enc.root, enc2.root);
+
+        let mut cfr = Cfr::with_variant(&enc, Variant::Linear);
+        for _ in 0..10 {
+            cfr.step(&enc);
+        }
+        let book = StrategyBook::from_average_strategy(&enc, &cfr.average_strategy(&enc));
+        let mut buf = Vec::new();
+        book.write_to(&mut buf).unwrap();
+        let book2: StrategyBook<Action, Vec<Inflet>> = StrategyBook::read_from(&mut buf.as_slice()).unwrap();
+        assert_eq!(book2.len(), enc.infosets.len());
+        for inf in &enc.infosets {
+            assert_eq!(book.get(&inf.orig), book2.get(&inf.orig));
+        }
+        assert!(book2.get(&vec![]).is_none());
+        let mut buf2 = Vec::new();
+        book2.write_to(&mut buf2).unwrap();
+        assert_eq!(buf, buf2, "reproducible");
+
+        // truncated
+        assert!(StrategyBook::<Action, Vec<Inflet>>::read_from(&mut &buf[..buf.len() - 1]).is_err());
+        // written by another version
+        buf[4] += 1;
+        let e = StrategyBook::<Action, Vec<Inflet>>::read_from(&mut buf.as_slice()).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        // not a strategy file
+        let mut buf = Vec::new();
+        write_encoding(&enc, &mut buf).unwrap();
+        assert!(StrategyBook::<Action, Vec<Inflet>>::read_from(&mut buf.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_encoding() {
+        let mut evaluator = MaterialEvaluator { info_penalty: DEFAULT_INFO_PENALTY };
+        let mut game = RbcGame::new(1 + 2, &mut evaluator, State::ChoosePositionBeforeSense(Color::Black), vec![
+            BoardState::initial(),
+            fen::BoardState::from_fen("rnbqkb1r/pppppppp/8/8/8/5n2/PPPPPPPP/RNBQKBNR w KQkq - 0 0").unwrap().into(),
+        ]);
+        let enc = Encoding::new(&mut game);
+        let read = |enc: &Encoding<RbcGame>| {
+            let mut buf = Vec::new();
+            write_encoding(enc, &mut buf).unwrap();
+            read_encoding::<RbcGame>(&mut buf.as_slice()).map(|_| ())
+        };
+        assert!(read(&enc).is_ok());
+        let choice = enc.nodes.iter().position(|r| r.kind == 0 || r.kind == 1).unwrap();
+
+        let mut bad = Encoding::new(&mut game);
+        bad.nodes[choice].num_children -= 1;
+        assert_eq!(read(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut bad = Encoding::new(&mut game);
+        bad.nodes[choice].kind = 1 - bad.nodes[choice].kind;
+        assert_eq!(read(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut bad = Encoding::new(&mut game);
+        bad.parents.pop();
+        assert_eq!(read(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut stripped = Encoding::new(&mut game);
+        stripped.strip_debug_info();
+        assert!(read(&stripped).is_ok());
+
+        // truncated
+        let mut buf = Vec::new();
+        write_encoding(&enc, &mut buf).unwrap();
+        assert!(read_encoding::<RbcGame>(&mut &buf[..buf.len() - 1]).is_err());
+    }
+}
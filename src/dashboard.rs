@@ -0,0 +1,171 @@
+// Status of a running client: games in progress and results so far,
+// rendered as a page that lives next to the game logs.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Instant;
+use crate::game::Color;
+
+pub struct GameInfo {
+    pub slot: usize,
+    // None if the server wouldn't tell
+    pub opponent: Option<String>,
+    pub color: Color,
+    pub ply: usize,
+    pub seconds_left: f64,
+    pub possible_states: usize,
+    pub started: Instant,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub errors: u32,
+}
+
+#[derive(Default)]
+pub struct Dashboard {
+    pub games: BTreeMap<i32, GameInfo>,
+    pub records: BTreeMap<String, Record>,
+    // games against opponents we never learned the name of
+    pub unknown: Record,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Dashboard {
+    pub fn start_game(&mut self, game_id: i32, slot: usize, color: Color, opponent: Option<String>) {
+        self.games.insert(game_id, GameInfo {
+            slot,
+            opponent,
+            color,
+            ply: 0,
+            seconds_left: 900.0,
+            possible_states: 1,
+            started: Instant::now(),
+        });
+    }
+
+    // outcome as returned by play_game(): 'W', 'L', 'D' or 'E'
+    pub fn finish_game(&mut self, game_id: i32, outcome: char) {
+        let info = match self.games.remove(&game_id) {
+            Some(info) => info,
+            None => return,
+        };
+        let r = match info.opponent {
+            Some(name) => self.records.entry(name).or_default(),
+            None => &mut self.unknown,
+        };
+        match outcome {
+            'W' => r.wins += 1,
+            'L' => r.losses += 1,
+            'D' => r.draws += 1,
+            _ => r.errors += 1,
+        }
+    }
+
+    pub fn total(&self) -> Record {
+        let mut total = Record::default();
+        for r in self.records.values().chain(Some(&self.unknown)) {
+            total.wins += r.wins;
+            total.losses += r.losses;
+            total.draws += r.draws;
+            total.errors += r.errors;
+        }
+        total
+    }
+
+    // Plain text, for the end of a session.
+    pub fn summary(&self) -> String {
+        let total = self.total();
+        let mut s = format!("{} games: {} wins, {} losses, {} draws, {} errors",
+            total.wins + total.losses + total.draws + total.errors,
+            total.wins, total.losses, total.draws, total.errors);
+        if !self.games.is_empty() {
+            write!(s, "; {} unfinished", self.games.len()).unwrap();
+        }
+        for (name, r) in &self.records {
+            write!(s, "\n  {:20} +{} -{} ={} E{}", name, r.wins, r.losses, r.draws, r.errors).unwrap();
+        }
+        s
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, r#"<meta charset="utf-8">"#).unwrap();
+        writeln!(s, r#"<meta http-equiv="refresh" content="5">"#).unwrap();
+        writeln!(s, r#"<link href="../static/style.css" rel="stylesheet">"#).unwrap();
+        writeln!(s, "<p>{}</p>", chrono::offset::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")).unwrap();
+
+        writeln!(s, "<h3>Games</h3>").unwrap();
+        writeln!(s, "<table>").unwrap();
+        writeln!(s, "<tr><td>slot</td><td>game</td><td>opponent</td><td>color</td><td>ply</td>\
+                     <td>clock</td><td>states</td><td>running</td></tr>").unwrap();
+        let mut games: Vec<_> = self.games.iter().collect();
+        games.sort_by_key(|(_, info)| info.slot);
+        for (game_id, info) in games {
+            writeln!(s, r#"<tr><td class=numcol>{}</td><td><a href="game_{:05}.html">{}</a></td><td>{}</td><td>{:?}</td>
+                <td class=numcol>{}</td><td class=numcol>{:.0}s</td><td class=numcol>{}</td><td class=numcol>{:.0}s</td></tr>"#,
+                info.slot, game_id, game_id,
+                escape(info.opponent.as_deref().unwrap_or("?")),
+                info.color, info.ply, info.seconds_left, info.possible_states,
+                info.started.elapsed().as_secs_f64()).unwrap();
+        }
+        writeln!(s, "</table>").unwrap();
+
+        writeln!(s, "<h3>Results</h3>").unwrap();
+        writeln!(s, "<table>").unwrap();
+        writeln!(s, "<tr><td>opponent</td><td>W</td><td>L</td><td>D</td><td>E</td></tr>").unwrap();
+        let total = self.total();
+        for (name, r) in self.records.iter().map(|(n, r)| (escape(n), r)).chain(Some(("<b>total</b>".to_owned(), &total))) {
+            writeln!(s, "<tr><td>{}</td><td class=numcol>{}</td><td class=numcol>{}</td>\
+                         <td class=numcol>{}</td><td class=numcol>{}</td></tr>",
+                name, r.wins, r.losses, r.draws, r.errors).unwrap();
+        }
+        writeln!(s, "</table>").unwrap();
+        s
+    }
+
+    // Replaces the file at once, so that a reader never sees half a page.
+    pub fn write_to(&self, path: &str) -> std::io::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, self.to_html())?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard() {
+        let mut d = Dashboard::default();
+        d.start_game(1, 0, Color::White, Some("alice".to_owned()));
+        d.start_game(2, 1, Color::Black, None);
+        d.start_game(3, 2, Color::Black, Some("<bob>".to_owned()));
+        d.games.get_mut(&2).unwrap().possible_states = 1234;
+        let html = d.to_html();
+        assert!(html.contains(r#"<a href="game_00002.html">2</a>"#));
+        assert!(html.contains("1234"));
+        assert!(html.contains("&lt;bob&gt;"));
+
+        d.finish_game(1, 'W');
+        d.games.get_mut(&2).unwrap().opponent = Some("alice".to_owned());
+        d.finish_game(2, 'L');
+        d.finish_game(3, 'E');
+        d.finish_game(3, 'W');  // already gone
+        d.start_game(4, 0, Color::White, None);
+        d.finish_game(4, 'E');
+        assert!(d.games.is_empty());
+        assert_eq!(d.records["alice"], Record { wins: 1, losses: 1, draws: 0, errors: 0 });
+        assert_eq!(d.records.len(), 2, "no row for unknown opponents");
+        assert_eq!(d.unknown, Record { wins: 0, losses: 0, draws: 0, errors: 1 });
+        assert_eq!(d.total(), Record { wins: 1, losses: 1, draws: 0, errors: 2 });
+        assert!(d.summary().starts_with("4 games: 1 wins, 1 losses, 0 draws, 2 errors\n"), "{}", d.summary());
+    }
+}
@@ -154,8 +154,19 @@ pub struct Stats {
     pub tt_use_best_move: i64,
     pub tt_best_move_not_found: i64,
     pub tt_best_move_corrupt: i64,
+    pub killer_cutoffs: i64,
+    pub quiet_cutoffs: i64,
+    pub null_move_tries: i64,
+    pub null_move_cutoffs: i64,
+    pub lmr_reductions: i64,
+    pub lmr_researches: i64,
+    pub check_extensions: i64,
 }
 
+const NULL_MOVE_REDUCTION: i32 = 2;
+const LMR_MIN_MOVE_INDEX: usize = 3;
+const HISTORY_MAX: i32 = 1 << 20;
+
 pub struct Ctx {
     state: crate::fast::State,
     undo_log: Vec<crate::fast::UndoEntry>,
@@ -166,6 +177,22 @@ pub struct Ctx {
     pub print: bool,
     pub expensive_eval: bool,
     pub stats: Stats,
+
+    // Search enhancements, all off by default.
+    pub killer_moves: bool,
+    pub history_heuristic: bool,
+    pub null_move_pruning: bool,
+    pub late_move_reductions: bool,
+    pub check_extensions: bool,
+    killers: Vec<[crate::fast::Move; 2]>,  // by ply
+    history: Vec<i32>,  // by (color, from, to)
+    skip_null_move: bool,
+    root_depth: i32,
+
+    // Polled during search; when set, search() unwinds as fast as possible
+    // and its result should be discarded (see aborted()).
+    pub stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    aborted: bool,
 }
 
 impl Ctx {
@@ -180,6 +207,17 @@ impl Ctx {
             print: false,
             expensive_eval: false,
             stats: Stats::default(),
+            killer_moves: false,
+            history_heuristic: false,
+            null_move_pruning: false,
+            late_move_reductions: false,
+            check_extensions: false,
+            killers: Vec::new(),
+            history: vec![0; 2 * 64 * 64],
+            skip_null_move: false,
+            root_depth: 0,
+            stop: Default::default(),
+            aborted: false,
         }
     }
     pub fn reset(&mut self, board: BoardState) {
@@ -187,7 +225,22 @@ impl Ctx {
         assert!(self.undo_log.is_empty());
         assert!(self.moves.is_empty());
         self.state = (&board).into();
+        self.aborted = false;
     }
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+    pub fn enable_all_enhancements(&mut self) {
+        self.killer_moves = true;
+        self.history_heuristic = true;
+        self.null_move_pruning = true;
+        self.late_move_reductions = true;
+        self.check_extensions = true;
+    }
+}
+
+fn history_index(color: Color, m: crate::fast::Move) -> usize {
+    (color as usize * 64 + m.from_sq().0 as usize) * 64 + m.to_sq().0 as usize
 }
 
 macro_rules! tree_println {
@@ -200,13 +253,26 @@ macro_rules! tree_println {
 }
 
 #[allow(clippy::cognitive_complexity)]
-pub fn search(depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
+pub fn search(mut depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
     assert!(alpha < beta);
     while ctx.pvs.len() <= ctx.ply {
         ctx.pvs.push(Vec::new());
     }
+    while ctx.killers.len() <= ctx.ply {
+        ctx.killers.push([crate::fast::Move::null(); 2]);
+    }
     ctx.pvs[ctx.ply].clear();
     ctx.stats.nodes += 1;
+    let allow_null_move = !std::mem::replace(&mut ctx.skip_null_move, false);
+    if ctx.ply == 0 {
+        ctx.root_depth = depth;
+    }
+    if ctx.stats.nodes & 1023 == 0 && ctx.stop.load(std::sync::atomic::Ordering::Relaxed) {
+        ctx.aborted = true;
+    }
+    if ctx.aborted {
+        return alpha;
+    }
 
     let color = ctx.state.side_to_play();
     let king = match ctx.state.find_king(color) {
@@ -224,9 +290,38 @@ pub fn search(depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
         return (10000 - 1 - ctx.ply as i32).max(alpha).min(beta);
     }
 
+    let in_check = ctx.state.can_attack_to(king, color.opposite());
+    if ctx.check_extensions && in_check && depth >= 1 &&
+       ctx.ply as i32 + depth < 2 * ctx.root_depth {
+        ctx.stats.check_extensions += 1;
+        depth += 1;
+    }
+
+    // Passing is legal in RBC, so unlike in chess there is no zugzwang
+    // to worry about.
+    if ctx.null_move_pruning && allow_null_move && depth > NULL_MOVE_REDUCTION &&
+       ctx.ply > 0 && !in_check {
+        ctx.stats.null_move_tries += 1;
+        let m = crate::fast::Move::null();
+        ctx.state.make_move(m, &mut ctx.undo_log);
+        ctx.ply += 1;
+        ctx.skip_null_move = true;
+        let t = -search(depth - 1 - NULL_MOVE_REDUCTION, -beta, -beta + 1, ctx);
+        ctx.ply -= 1;
+        ctx.state.unmake_move(m, &mut ctx.undo_log);
+        if ctx.aborted {
+            return alpha;
+        }
+        if t >= beta {
+            tree_println!(ctx, "null move cutoff {}", t);
+            ctx.stats.null_move_cutoffs += 1;
+            return beta;
+        }
+    }
+
     let moves_start = ctx.moves.len();
     tree_println!(ctx, "alpha={} beta={}", alpha, beta);
-    let moves_end = if depth == 0 && !ctx.state.can_attack_to(king, color.opposite()) {
+    let moves_end = if depth == 0 && !in_check {
         ctx.stats.q_branch += 1;
         let static_val = if ctx.expensive_eval {
             standing_pat(&ctx.state, color)
@@ -266,7 +361,9 @@ pub fn search(depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
         ctx.stats.full_branch += 1;
         ctx.moves.len()
     };
+    let killers = ctx.killers[ctx.ply];
     if depth >= 1 {
+        let mut ordered_start = moves_start;
         let idx = ctx.state.hash() as usize & ctx.tt.len() - 1;
         let tt_entry = &ctx.tt[idx];
         if tt_entry.hash_matches(ctx.state.hash()) {
@@ -275,6 +372,7 @@ pub fn search(depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
                     if let Some(i) = ctx.moves[moves_start..].iter().position(|&m| m == best_move) {
                         ctx.moves.swap(moves_start, moves_start + i);
                         ctx.stats.tt_use_best_move += 1;
+                        ordered_start += 1;
                     } else {
                         ctx.stats.tt_best_move_not_found += 1;
                     }
@@ -284,16 +382,56 @@ pub fn search(depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
         } else {
             ctx.stats.tt_miss += 1;
         }
+
+        if ctx.killer_moves || ctx.history_heuristic {
+            // captures, then killers, then quiet moves by history score
+            let use_killers = ctx.killer_moves;
+            let history = if ctx.history_heuristic { &ctx.history[..] } else { &[] };
+            ctx.moves[ordered_start..moves_end].sort_by_cached_key(|&m| {
+                -if m.is_tactical() {
+                    HISTORY_MAX + 3
+                } else if use_killers && m == killers[0] {
+                    HISTORY_MAX + 2
+                } else if use_killers && m == killers[1] {
+                    HISTORY_MAX + 1
+                } else if history.is_empty() {
+                    0
+                } else {
+                    history[history_index(color, m)]
+                }
+            });
+        }
     }
     let mut best_move = crate::fast::Move::null();
     for i in moves_start..moves_end {
         let m = ctx.moves[i];
+        let reduce = ctx.late_move_reductions &&
+            depth >= 3 &&
+            i - moves_start >= LMR_MIN_MOVE_INDEX &&
+            !in_check &&
+            !m.is_tactical() &&
+            !killers.contains(&m);
         ctx.state.make_move(m, &mut ctx.undo_log);
         ctx.ply += 1;
-        let t = -search((depth - 1).max(0), -beta, -alpha, ctx);
+        let t = if reduce {
+            ctx.stats.lmr_reductions += 1;
+            let t = -search(depth - 2, -alpha - 1, -alpha, ctx);
+            if t > alpha && !ctx.aborted {
+                ctx.stats.lmr_researches += 1;
+                -search(depth - 1, -beta, -alpha, ctx)
+            } else {
+                t
+            }
+        } else {
+            -search((depth - 1).max(0), -beta, -alpha, ctx)
+        };
         assert_eq!(ctx.moves.len(), moves_end);
         ctx.ply -= 1;
         ctx.state.unmake_move(m, &mut ctx.undo_log);
+        if ctx.aborted {
+            ctx.moves.truncate(moves_start);
+            return alpha;
+        }
         if t > alpha {
             best_move = m;
             ctx.pvs[ctx.ply].clear();
@@ -307,6 +445,25 @@ pub fn search(depth: i32, mut alpha: i32, beta: i32, ctx: &mut Ctx) -> i32 {
         if t >= beta {
             tree_println!(ctx, "ev {:?} cutoff {}", m, t);
 
+            if depth >= 1 && !m.is_tactical() {
+                ctx.stats.quiet_cutoffs += 1;
+                if killers.contains(&m) {
+                    ctx.stats.killer_cutoffs += 1;
+                }
+                if ctx.killer_moves && killers[0] != m {
+                    ctx.killers[ctx.ply] = [m, killers[0]];
+                }
+                if ctx.history_heuristic {
+                    let h = &mut ctx.history[history_index(color, m)];
+                    *h += depth * depth;
+                    if *h >= HISTORY_MAX {
+                        for h in &mut ctx.history {
+                            *h /= 2;
+                        }
+                    }
+                }
+            }
+
             if depth >= 1 {
                 let idx = ctx.state.hash() as usize & ctx.tt.len() - 1;
                 ctx.tt[idx] = TtEntry::new(ctx.state.hash(), best_move.to_simple_move().unwrap());
@@ -350,3 +507,39 @@ fn test_quiescence() {
     dbg!(q);
     dbg!(&ctx.pvs[0]);
 }
+
+// Node counts for iterative deepening on the bin/tree.rs positions.
+// The exact numbers are expected to change when search or evaluation does;
+// re-record them then, but make sure the enhancements still pay off.
+#[cfg(test)]
+#[test]
+fn test_search_node_counts() {
+    fn nodes(fen: &str, depth: i32, enhancements: bool) -> (i64, i32) {
+        let board: BoardState = fen::BoardState::from_fen(fen).unwrap().into();
+        let mut ctx = Ctx::new(board.clone());
+        ctx.expensive_eval = true;
+        if enhancements {
+            ctx.enable_all_enhancements();
+        }
+        let mut total = 0;
+        let mut val = 0;
+        for d in 0..=depth {
+            ctx.reset(board.clone());
+            val = search(d, -10000, 10000, &mut ctx);
+            total += ctx.stats.nodes;
+        }
+        (total, val)
+    }
+    let opening = "r1bqkbnr/ppp2ppp/2np4/4p3/2B1P3/2N2N2/PPPP1PPP/R1BQK2R b KQkq - 0 0";
+    let middle = "r2qk2r/p1p2pbp/2npbp1n/4p3/2B1P3/2N1BNQ1/PPP2PPP/R4RK1 b kq - 0 0";
+    for &(fen, expected_baseline, expected_enhanced) in &[
+        (opening, (50107, -23), (23576, -23)),
+        (middle, (794474, -48), (34122, -48)),
+    ] {
+        let baseline = nodes(fen, 4, false);
+        let enhanced = nodes(fen, 4, true);
+        assert_eq!(baseline, expected_baseline);
+        assert_eq!(enhanced, expected_enhanced);
+        assert!(enhanced.0 < baseline.0);
+    }
+}
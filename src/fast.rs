@@ -37,6 +37,15 @@ impl Move {
         Square(self.to() as i8)
     }
     #[allow(clippy::wrong_self_convention)]
+    pub fn from_sq(self) -> Square {
+        Square(self.from() as i8)
+    }
+    // Capture or promotion.
+    // En passant captures don't count, because cap is not set for them.
+    pub fn is_tactical(self) -> bool {
+        self.cap() != 0 || self.from_kind() != self.to_kind()
+    }
+    #[allow(clippy::wrong_self_convention)]
     fn from_kind(self) -> u32 {
         (self.0 >> 12) & 7
     }
@@ -247,6 +256,54 @@ impl State {
         }
     }
 
+    // Same squares in the same order as BoardState::sense().
+    pub fn sense(&self, sq: Square) -> Vec<(Square, Option<Piece>)> {
+        let mask = sense_mask(sq);
+        let mut result = Vec::with_capacity(9);
+        let r = sq.0 / 8;
+        for r in (0.max(r - 1)..=7.min(r + 1)).rev() {
+            for q in iter_one_positions(mask & 0xff << (8 * r)) {
+                let q = Square(q as i8);
+                result.push((q, self.get_piece(q)));
+            }
+        }
+        result
+    }
+
+    // Two states have the same fingerprint for a given square iff
+    // they have the same BoardState::sense_fingerprint(),
+    // but the values themselves are different.
+    // Three 9-bit windows, one for each bit of get_opt_kind().
+    pub fn sense_fingerprint(&self, sq: Square) -> u32 {
+        sense_window(self.by_kind[0] | self.by_kind[2] | self.by_kind[4], sq) |
+        sense_window(self.by_kind[1] | self.by_kind[2] | self.by_kind[5], sq) << 9 |
+        sense_window(self.by_kind[3] | self.by_kind[4] | self.by_kind[5], sq) << 18
+    }
+
+    // Same as BoardState::fog_of_war().
+    pub fn fog_of_war(&mut self, color: Color) {
+        let pre: &Precomputed = &PRECOMPUTED;
+        let opp = 1 - color as usize;
+        for kind in 0..6 {
+            for sq in iter_one_positions(self.by_kind[kind] & self.by_color[opp]) {
+                self.hash ^= pre.zobrist[(kind * 2 + opp) * 64 + sq as usize];
+            }
+            self.by_kind[kind] &= !self.by_color[opp];
+        }
+        self.by_color[opp] = 0;
+
+        self.hash ^= pre.zobrist_castling[(self.flags & 15) as usize];
+        self.hash ^= pre.zobrist_ep[self.ep_file as usize];
+        self.flags |= match color {
+            Color::White => 4 | 8,
+            Color::Black => 1 | 2,
+        };
+        self.ep_file = 8;
+        self.hash ^= pre.zobrist_castling[(self.flags & 15) as usize];
+        self.hash ^= pre.zobrist_ep[self.ep_file as usize];
+        debug_assert!(self.check());
+    }
+
     fn get_kind(&self, sq: i8) -> u32 {
         debug_assert!(((self.by_color[0] | self.by_color[1]) >> sq) & 1 != 0);
         let kind =
@@ -1256,6 +1313,93 @@ impl Iterator for BitsIter {
     }
 }
 
+// Squares revealed by sensing at sq: the 3x3 window clipped to the board.
+pub fn sense_mask(sq: Square) -> u64 {
+    PRECOMPUTED.king_attacks[sq.0 as usize] | 1 << sq.0
+}
+
+// Packs the 3x3 window of x centered at sq into 9 bits,
+// three bits per rank starting from the lower one.
+// Bits outside the board are 0.
+pub fn sense_window(x: u64, sq: Square) -> u32 {
+    let x = x & sense_mask(sq);
+    let shift = sq.0 as i32 - 9;
+    let x = if shift >= 0 { x >> shift } else { x << -shift };
+    (x & 7 | x >> 5 & 7 << 3 | x >> 10 & 7 << 6) as u32
+}
+
 pub fn iter_one_positions(x: u64) -> impl Iterator<Item=u32> {
     BitsIter(x).map(u64::trailing_zeros)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Perft fixture positions and everything reachable from them in two plies.
+    fn positions() -> Vec<BoardState> {
+        let mut result = Vec::new();
+        for f in crate::perft::parse_fixtures(crate::perft::POSITIONS) {
+            let b: BoardState = fen::BoardState::from_fen(&f.fen).unwrap().into();
+            for m1 in b.all_moves() {
+                let mut b1 = b.clone();
+                b1.make_move(m1);
+                if b1.winner().is_some() {
+                    continue;
+                }
+                for m2 in b1.all_moves() {
+                    let mut b2 = b1.clone();
+                    b2.make_move(m2);
+                    result.push(b2);
+                }
+            }
+            result.push(b);
+        }
+        result
+    }
+
+    #[test]
+    fn test_sense_window() {
+        for sq in (0..64).map(Square) {
+            assert_eq!(sense_window(!0, sq).count_ones(), sense_mask(sq).count_ones());
+            for q in iter_one_positions(sense_mask(sq)) {
+                let q = Square(q as i8);
+                let bit = (q.0 / 8 - sq.0 / 8 + 1) * 3 + q.0 % 8 - sq.0 % 8 + 1;
+                assert_eq!(sense_window(1 << q.0, sq), 1 << bit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sense_equivalence() {
+        let boards = positions();
+        let states: Vec<State> = boards.iter().map(Into::into).collect();
+        for sq in (0..64).map(Square) {
+            let mut fingerprints = fnv::FnvHashMap::<u32, u32>::default();
+            for (b, s) in boards.iter().zip(&states) {
+                assert_eq!(s.sense(sq), b.sense(sq));
+                let fp = s.sense_fingerprint(sq);
+                let naive_fp = b.sense_fingerprint(sq);
+                assert_eq!(*fingerprints.entry(fp).or_insert(naive_fp), naive_fp);
+            }
+            // and the other way around
+            let distinct: fnv::FnvHashSet<u32> = fingerprints.values().cloned().collect();
+            assert_eq!(distinct.len(), fingerprints.len());
+        }
+    }
+
+    #[test]
+    fn test_fog_of_war_equivalence() {
+        for b in positions() {
+            for &color in &[Color::White, Color::Black] {
+                let mut s: State = (&b).into();
+                s.fog_of_war(color);
+                assert!(s.check());
+                let mut expected = b.clone();
+                expected.fog_of_war(color);
+                assert_eq!(BoardState::from(&s), expected);
+                assert_eq!(s, State::from(&expected));
+            }
+        }
+    }
+}
@@ -270,6 +270,25 @@ impl BoardState {
         fen::BoardState::from_fen(STARTING_FEN).unwrap().into()
     }
 
+    // Move counters aren't tracked, they are always "0 1".
+    pub fn to_fen(&self) -> String {
+        fen::BoardState {
+            pieces: (0..64).map(|i| self.get_piece(Square(i))
+                .map(|p| fen::Piece::from_char(p.to_char()).unwrap())).collect(),
+            side_to_play: match self.side_to_play() {
+                Color::White => fen::Color::White,
+                Color::Black => fen::Color::Black,
+            },
+            white_can_oo: self.flags.contains(BoardFlags::WHITE_CAN_OO),
+            white_can_ooo: self.flags.contains(BoardFlags::WHITE_CAN_OOO),
+            black_can_oo: self.flags.contains(BoardFlags::BLACK_CAN_OO),
+            black_can_ooo: self.flags.contains(BoardFlags::BLACK_CAN_OOO),
+            en_passant_square: self.en_passant_square.map(|s| s.0 as u8),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }.to_fen()
+    }
+
     pub fn side_to_play(&self) -> Color {
         if self.flags.contains(BoardFlags::WHITE_TO_PLAY) {
             Color::White
@@ -519,4 +538,12 @@ mod tests {
         assert_eq!(Move { from: Square(8), to: Square(2), promotion: None }.to_uci(), "a2c1");
         assert_eq!(Move { from: Square(8), to: Square(2), promotion: Some(PieceKind::Queen) }.to_uci(), "a2c1q");
     }
+
+    #[test]
+    fn test_to_from_fen() {
+        assert_eq!(BoardState::initial().to_fen(), STARTING_FEN);
+        let fen = "rnbqkb1r/pppp1ppp/5n2/4p3/4P3/8/PPPP1PPP/RNBQK1NR w Qk e6 0 1";
+        let board: BoardState = fen::BoardState::from_fen(fen).unwrap().into();
+        assert_eq!(board.to_fen(), fen);
+    }
 }
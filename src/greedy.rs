@@ -5,30 +5,83 @@ use rand::prelude::*;
 use crate::game::{Square, Color, Piece, Move, BoardState};
 use crate::ai_interface::{Ai, Player};
 use crate::infoset::Infoset;
+use crate::session::Outcome;
+use crate::cfr::Solver;
+use crate::rbc_xf::{RbcGame, Action, Inflet};
+use crate::cfr_io::StrategyBook;
+use crate::time_manager::{TimeManager, Phase, Deadline};
+
+// Largest infoset worth solving with CFR, per second of decision budget.
+const CFR_SENSE_STATES_PER_SECOND: f64 = 50.0;
+const CFR_MOVE_STATES_PER_SECOND: f64 = 2.0;
+// How much longer the next search depth is expected to take than all
+// the previous ones, see Deadline::can_deepen().
+// CFR needs most of the budget after building the tree.
+const CFR_DEEPENING_GROWTH: f64 = 5.0;
+const SEARCH_DEEPENING_GROWTH: f64 = 7.0;
+
+// Written by src/bin/opening.rs.
+const OPENING_BOOK_PATH: &str = "data/opening.strategy";
+
+type OpeningBook = StrategyBook<Action, Vec<Inflet>>;
+
+lazy_static::lazy_static! {
+    // None if there is no book, players then use the built-in openings.
+    static ref OPENING_BOOK: Option<OpeningBook> = match StrategyBook::load(OPENING_BOOK_PATH) {
+        Ok(book) => {
+            info!("{} infosets in the opening book", book.len());
+            Some(book)
+        }
+        Err(e) => {
+            info!("no opening book at {}: {}", OPENING_BOOK_PATH, e);
+            None
+        }
+    };
+}
 
 #[derive(Clone)]
 pub struct GreedyAi {
     pub experiment: bool,
+    pub cfr_variant: crate::cfr::Variant,
+    // Stop solving early once the strategy is this close to equilibrium.
+    pub cfr_target_nash_conv: Option<f32>,
+    // Use cfr::ParallelCfr. Ignored without the "heavy" feature.
+    pub cfr_parallel: bool,
 }
 
 impl Ai for GreedyAi {
-    fn make_player(&self, color: Color, seed: u64) -> Box<dyn Player> {
+    fn make_player(&self, color: Color, seed: u64, increment: f64) -> Box<dyn Player> {
+        Box::new(self.player(color, seed, increment))
+    }
+}
+
+impl GreedyAi {
+    fn player(&self, color: Color, seed: u64, increment: f64) -> GreedyPlayer {
         let mut ctx = crate::eval::Ctx::new(BoardState::initial());
         ctx.expensive_eval = true;
-        Box::new(GreedyPlayer {
+        // Move ordering only, so search results are the same as without them.
+        ctx.killer_moves = true;
+        ctx.history_heuristic = true;
+        GreedyPlayer {
             rng: StdRng::seed_from_u64(seed),
             color,
             summary: Vec::new(),
             experiment: self.experiment,
+            cfr_variant: self.cfr_variant,
+            cfr_target_nash_conv: self.cfr_target_nash_conv,
+            cfr_parallel: self.cfr_parallel,
             move_number: match color {
                 Color::White => 0,
                 Color::Black => 1,
             },
             last_capture: None,
             ctx,
+            time: TimeManager::new(increment),
             last_sense_result: None,
             strategy: None,
-        })
+            opening_book: OPENING_BOOK.as_ref(),
+            observation: vec![Inflet::MyColor(color)],
+        }
     }
 }
 
@@ -39,11 +92,33 @@ struct GreedyPlayer {
     color: Color,
     summary: Vec<u8>,
     #[allow(dead_code)] experiment: bool,
+    cfr_variant: crate::cfr::Variant,
+    cfr_target_nash_conv: Option<f32>,
+    cfr_parallel: bool,
     move_number: i32,
     last_capture: Option<Piece>,
     ctx: crate::eval::Ctx,
+    time: TimeManager,
     last_sense_result: Option<SenseResult>,
     strategy: Option<HashMap<SenseResult, (f32, f32, Vec<(Option<Move>, f32)>)>>,
+    opening_book: Option<&'static OpeningBook>,
+    // What the player has seen so far, in the terms of the opening book.
+    observation: Vec<Inflet>,
+}
+
+#[cfg_attr(not(feature = "heavy"), allow(unused_variables))]
+fn make_solver<'a>(
+    enc: &crate::cfr::Encoding<RbcGame<'a>>, variant: crate::cfr::Variant, parallel: bool,
+) -> Box<dyn Solver<RbcGame<'a>>> {
+    #[cfg(feature = "heavy")]
+    if parallel {
+        return Box::new(crate::cfr::ParallelCfr::with_variant(enc, variant));
+    }
+    Box::new(crate::cfr::Cfr::with_variant(enc, variant))
+}
+
+fn hard_stop_set(ctx: &crate::eval::Ctx) -> bool {
+    ctx.stop.load(std::sync::atomic::Ordering::Relaxed)
 }
 
 fn sparsen<T>(max_size: usize, rng: &mut StdRng, it: impl ExactSizeIterator<Item=T>) -> Vec<T> {
@@ -62,31 +137,28 @@ fn sparsen<T>(max_size: usize, rng: &mut StdRng, it: impl ExactSizeIterator<Item
     result
 }
 
-impl Player for GreedyPlayer {
-    fn begin(&mut self, _html: &mut dyn Write) {}
-
-    fn handle_opponent_move(&mut self,
-        capture: Option<(Square, Piece)>,
-        infoset: &Infoset,
-        html: &mut dyn Write,
-    ) {
-        assert_eq!(self.color, infoset.fog_state.side_to_play());
-        info!("opp capture: {:?}", capture);
-        info!("{} possible states", infoset.possible_states.len());
-        if let Some((cs, piece)) = capture {
-            writeln!(html, "<p>Opponent captured {} at <b>{:?}</b>.</p>", piece.to_emoji(), cs).unwrap();
-            self.last_capture = Some(piece);
-        } else {
-            self.last_capture = None;
+impl GreedyPlayer {
+    // Opening book strategy for the game so far, keeping the actions
+    // that `f` accepts. None if the book doesn't cover this infoset.
+    fn book_strategy<T>(&self, f: impl Fn(&Action) -> Option<T>) -> Option<Vec<(T, f32)>> {
+        let s = self.opening_book?.get(&self.observation)?;
+        let d: Vec<(T, f32)> = s.iter()
+            .filter(|(_, p)| *p > 0.0)
+            .filter_map(|(a, p)| f(a).map(|x| (x, *p)))
+            .collect();
+        if d.is_empty() {
+            return None;
         }
+        Some(d)
     }
 
-    fn choose_sense(&mut self, remaining_time: f64, infoset: &Infoset, html: &mut dyn Write) -> Vec<(Square, f32)> {
+    fn choose_sense_within(&mut self,
+        remaining_time: f64, deadline: &Deadline, infoset: &Infoset, html: &mut dyn Write,
+    ) -> Vec<(Square, f32)> {
         info!("choose_sense (move {})", self.move_number);
         writeln!(html, r#"<h3 id="sense{}">Move {}</h3>"#, self.move_number, self.move_number).unwrap();
         writeln!(html, "<h4>Sense</h4>").unwrap();
-        let urgency = (remaining_time / 450.0).min(1.0).max(0.02);
-        writeln!(html, "<p>urgency = {}</p>", urgency).unwrap();
+        writeln!(html, "<p>budget {:.1}s, at most {:.1}s</p>", deadline.soft, deadline.hard).unwrap();
 
         assert_eq!(self.color, infoset.fog_state.side_to_play());
         write!(self.summary, "{:>6}", infoset.possible_states.len()).unwrap();
@@ -112,6 +184,15 @@ impl Player for GreedyPlayer {
             append_to_summary!(html, "<td class=numcol>---</td><td></td>");
             return vec![(sq, 1.0)];
         }
+        let book = self.book_strategy(|a| match *a {
+            Action::Sense(sq) => Some(sq),
+            _ => None,
+        });
+        if let Some(d) = book {
+            writeln!(html, "<p>from the opening book: {:?}</p>", d).unwrap();
+            append_to_summary!(html, "<td class=numcol>book</td><td></td>");
+            return d;
+        }
         if self.move_number == 1 {
             writeln!(html, "<p>opening</p>").unwrap();
             append_to_summary!(html, "<td class=numcol>open</td><td></td>");
@@ -124,31 +205,48 @@ impl Player for GreedyPlayer {
         }
 
         self.strategy = None;
-        if (possible_states.len() as f64) < 400.0 * urgency {
+        if (possible_states.len() as f64) < CFR_SENSE_STATES_PER_SECOND * deadline.soft {
             // TODO: dedup (anchor: vpMLtnvncYMi)
             let cfr_timer = std::time::Instant::now();
             let mut search_depth = 0;
+            let hard_stop = self.ctx.stop.clone();
             let strategy = loop {
+                let mut evaluator = crate::rbc_xf::SearchEvaluator::new(&mut self.ctx, search_depth);
                 let mut game = crate::rbc_xf::RbcGame::new(
-                    1 + 2, search_depth, &mut self.ctx,
+                    1 + 2, &mut evaluator,
                     crate::rbc_xf::State::ChoosePositionBeforeSense(self.color.opposite()),
                     possible_states.clone());
-                let enc = crate::cfr::Encoding::new(&mut game);
+                let mut enc = crate::cfr::Encoding::new(&mut game);
+                enc.strip_debug_info();
                 writeln!(html, "<p>Building game tree (search depth {}) took {:.3}s</p>", search_depth, cfr_timer.elapsed().as_secs_f64()).unwrap();
                 if search_depth == 0 {
-                    writeln!(html, "<p>{} nodes, {} infosets</p>", enc.nodes.len(), enc.infosets.len()).unwrap();
+                    writeln!(html, "<p>{} nodes, {} infosets, {:.1} bytes per node</p>",
+                        enc.num_nodes(), enc.infosets.len(), enc.heap_size() as f64 / enc.num_nodes() as f64).unwrap();
                 }
                 html.flush().unwrap();
 
-                if timer.elapsed().as_secs_f64() >= 1.5 * urgency || search_depth > 9 {
-                    let sol_timer = std::time::Instant::now();
-                    let mut cfr = crate::cfr::Cfr::new(&enc);
-                    for step in 0..1_000_000 {
+                // Past the hard deadline the leaves are only material, see SearchEvaluator.
+                let cut_off = hard_stop.load(std::sync::atomic::Ordering::Relaxed);
+                if cut_off {
+                    writeln!(html, "<p>cut off by the hard deadline</p>").unwrap();
+                }
+                if cut_off || !deadline.can_deepen(CFR_DEEPENING_GROWTH) || search_depth > 9 {
+                    let mut cfr = make_solver(&enc, self.cfr_variant, self.cfr_parallel);
+                    for step in 0..1_000_000u32 {
                         cfr.step(&enc);
-                        if sol_timer.elapsed().as_secs_f64() > 0.5 + 5.5 * urgency {
+                        if deadline.past_soft() {
                             writeln!(html, "CFR made {} iterations", step).unwrap();
                             break;
                         }
+                        if let Some(target) = self.cfr_target_nash_conv {
+                            if (step + 1).is_power_of_two() {
+                                let nc = crate::cfr::nash_conv(&enc, &cfr.average_strategy(&enc));
+                                if nc <= target {
+                                    writeln!(html, "CFR converged after {} iterations (nash conv {})", step + 1, nc).unwrap();
+                                    break;
+                                }
+                            }
+                        }
                     }
                     break cfr.get_strategy(&enc);
                 }
@@ -193,11 +291,19 @@ impl Player for GreedyPlayer {
         }
 
         let mut by_taken: fnv::FnvHashMap<BoardState, fnv::FnvHashMap<Option<Move>, i32>> = Default::default();
-        by_taken.reserve(possible_states.len());
+        // The first depth always completes, deeper ones are cut off by the hard deadline.
+        let hard_stop = std::mem::take(&mut self.ctx.stop);
         for depth in 0..10 {
-            by_taken.clear();
+            if depth == 1 {
+                self.ctx.stop = hard_stop.clone();
+            }
+            let mut next: fnv::FnvHashMap<BoardState, fnv::FnvHashMap<Option<Move>, i32>> = Default::default();
+            next.reserve(possible_states.len());
             for s in &possible_states {
-                let e = by_taken.entry(s.clone()).or_default();
+                if hard_stop_set(&self.ctx) {
+                    break;
+                }
+                let e = next.entry(s.clone()).or_default();
                 let all_moves = s.all_moves();
                 e.reserve(all_moves.len());
                 for m in all_moves {
@@ -208,9 +314,14 @@ impl Player for GreedyPlayer {
                     e.insert(m, score);
                 }
             }
+            if hard_stop_set(&self.ctx) {
+                writeln!(html, "<p>depth {} cut off by the hard deadline</p>", depth).unwrap();
+                break;
+            }
+            by_taken = next;
             writeln!(html, "<p>score by_taken (depth {}) took {:>5.1}s</p>", depth, timer.elapsed().as_secs_f64()).unwrap();
             html.flush().unwrap();
-            if timer.elapsed().as_secs_f64() >= 1.0 * urgency {
+            if !deadline.can_deepen(SEARCH_DEEPENING_GROWTH) {
                 break;
             }
         }
@@ -277,31 +388,29 @@ impl Player for GreedyPlayer {
             .collect()
     }
 
-    fn handle_sense(&mut self,
-        sense: Square, sense_result: &[(Square, Option<Piece>)],
-        infoset: &Infoset,
-        html: &mut dyn Write,
-    ) {
-        assert_eq!(self.color, infoset.fog_state.side_to_play());
-        self.last_sense_result = Some((sense, sense_result.to_owned()));
-        info!("sense {:?} -> {:?}", sense, sense_result);
-        info!("{} possible states", infoset.possible_states.len());
-        write!(self.summary, " {:>5}", infoset.possible_states.len()).unwrap();
-        append_to_summary!(html, "<td class=numcol>{}</td>", infoset.possible_states.len());
-        write!(html, "<p>{}</p>", infoset.to_html()).unwrap();
-        html.flush().unwrap();
-    }
-
-    fn choose_move(&mut self, remaining_time: f64, infoset: &Infoset, html: &mut dyn Write) -> Vec<(Option<Move>, f32)> {
+    fn choose_move_within(&mut self,
+        remaining_time: f64, deadline: &Deadline, infoset: &Infoset, html: &mut dyn Write,
+    ) -> Vec<(Option<Move>, f32)> {
         assert_eq!(self.color, infoset.fog_state.side_to_play());
         let timer = std::time::Instant::now();
         info!("choose_move (move {})", self.move_number);
         writeln!(html, r#"<h4 id="move{}">Move</h3>"#, self.move_number).unwrap();
-        let urgency = (remaining_time / 450.0).min(1.0).max(0.02);
-        writeln!(html, "<p>urgency = {}</p>", urgency).unwrap();
+        writeln!(html, "<p>budget {:.1}s, at most {:.1}s</p>", deadline.soft, deadline.hard).unwrap();
 
         append_to_summary!(html, "<td class=numcol><i>{:.0}s</i></td>", remaining_time);
 
+        let sensible_moves = infoset.fog_state.all_sensible_requested_moves();
+        let book = self.book_strategy(|a| match a {
+            Action::Move(m) if sensible_moves.contains(m) => Some(*m),
+            _ => None,
+        });
+        if let Some(d) = book {
+            self.strategy = None;
+            writeln!(html, "<p>from the opening book: {:?}</p>", d).unwrap();
+            append_to_summary!(html, "<td class=numcol>book</td><td></td>");
+            return d;
+        }
+
         if self.move_number == 0 {
             writeln!(html, "<p>opening</p>").unwrap();
             append_to_summary!(html, "<td class=numcol>open</td><td></td>");
@@ -330,31 +439,48 @@ impl Player for GreedyPlayer {
             }
         }
 
-        if (infoset.possible_states.len() as f64) < 15.0 * urgency {
+        if (infoset.possible_states.len() as f64) < CFR_MOVE_STATES_PER_SECOND * deadline.soft {
             // TODO: dedup (anchor: vpMLtnvncYMi)
             let cfr_timer = std::time::Instant::now();
             let mut search_depth = 0;
+            let hard_stop = self.ctx.stop.clone();
             let strategy = loop {
+                let mut evaluator = crate::rbc_xf::SearchEvaluator::new(&mut self.ctx, search_depth);
                 let mut game = crate::rbc_xf::RbcGame::new(
-                    1 + 3, search_depth, &mut self.ctx,
+                    1 + 3, &mut evaluator,
                     crate::rbc_xf::State::ChoosePositionBeforeMove(self.color.opposite()),
                     infoset.possible_states.clone());
-                let enc = crate::cfr::Encoding::new(&mut game);
+                let mut enc = crate::cfr::Encoding::new(&mut game);
+                enc.strip_debug_info();
                 writeln!(html, "<p>Building game tree (search depth {}) took {:.3}s</p>", search_depth, cfr_timer.elapsed().as_secs_f64()).unwrap();
                 if search_depth == 0 {
-                    writeln!(html, "<p>{} nodes, {} infosets</p>", enc.nodes.len(), enc.infosets.len()).unwrap();
+                    writeln!(html, "<p>{} nodes, {} infosets, {:.1} bytes per node</p>",
+                        enc.num_nodes(), enc.infosets.len(), enc.heap_size() as f64 / enc.num_nodes() as f64).unwrap();
                 }
                 html.flush().unwrap();
 
-                if timer.elapsed().as_secs_f64() >= 1.5 * urgency || search_depth > 9 {
-                    let sol_timer = std::time::Instant::now();
-                    let mut cfr = crate::cfr::Cfr::new(&enc);
-                    for step in 0..1_000_000 {
+                // Past the hard deadline the leaves are only material, see SearchEvaluator.
+                let cut_off = hard_stop.load(std::sync::atomic::Ordering::Relaxed);
+                if cut_off {
+                    writeln!(html, "<p>cut off by the hard deadline</p>").unwrap();
+                }
+                if cut_off || !deadline.can_deepen(CFR_DEEPENING_GROWTH) || search_depth > 9 {
+                    let mut cfr = make_solver(&enc, self.cfr_variant, self.cfr_parallel);
+                    for step in 0..1_000_000u32 {
                         cfr.step(&enc);
-                        if sol_timer.elapsed().as_secs_f64() > 0.5 + 5.5 * urgency {
+                        if deadline.past_soft() {
                             writeln!(html, "CFR made {} iterations", step).unwrap();
                             break;
                         }
+                        if let Some(target) = self.cfr_target_nash_conv {
+                            if (step + 1).is_power_of_two() {
+                                let nc = crate::cfr::nash_conv(&enc, &cfr.average_strategy(&enc));
+                                if nc <= target {
+                                    writeln!(html, "CFR converged after {} iterations (nash conv {})", step + 1, nc).unwrap();
+                                    break;
+                                }
+                            }
+                        }
                     }
                     break cfr.get_strategy(&enc);
                 }
@@ -398,11 +524,19 @@ impl Player for GreedyPlayer {
         }
         // TODO: dedup (anchor: TjifpfTOFCUV)
         let mut by_taken: fnv::FnvHashMap<BoardState, fnv::FnvHashMap<Option<Move>, CacheEntry>> = Default::default();
-        by_taken.reserve(states.len());
+        // The first depth always completes, deeper ones are cut off by the hard deadline.
+        let hard_stop = std::mem::take(&mut self.ctx.stop);
         for depth in 0..10 {
-            by_taken.clear();
+            if depth == 1 {
+                self.ctx.stop = hard_stop.clone();
+            }
+            let mut next: fnv::FnvHashMap<BoardState, fnv::FnvHashMap<Option<Move>, CacheEntry>> = Default::default();
+            next.reserve(states.len());
             for &s in &states {
-                let entry = by_taken.entry(s.clone()).or_default();
+                if hard_stop_set(&self.ctx) {
+                    break;
+                }
+                let entry = next.entry(s.clone()).or_default();
                 let all_moves = s.all_moves();
                 entry.reserve(all_moves.len());
                 for m in all_moves {
@@ -425,9 +559,14 @@ impl Player for GreedyPlayer {
                     entry.insert(m, e);
                 }
             }
+            if hard_stop_set(&self.ctx) {
+                writeln!(html, "<p>depth {} cut off by the hard deadline</p>", depth).unwrap();
+                break;
+            }
+            by_taken = next;
             writeln!(html, "<p>score by_taken (depth {}) took {:>5.1}s</p>", depth, timer.elapsed().as_secs_f64()).unwrap();
             html.flush().unwrap();
-            if timer.elapsed().as_secs_f64() >= 1.1 * urgency {
+            if !deadline.can_deepen(SEARCH_DEEPENING_GROWTH) {
                 break;
             }
         }
@@ -446,7 +585,10 @@ impl Player for GreedyPlayer {
         }
         html.flush().unwrap();
         info!("solving...");
-        let sol = fictitious_play(m, n, &payoff, 100_000);
+        let sol = crate::matrix_game::solve(m, n, &payoff, 1.0);
+        writeln!(html, "<p>duality gap {:.2}</p>", sol.duality_gap).unwrap();
+        // Rarely played moves are dropped below, the gap shows what that costs.
+        let sol = sol.pruned(m, n, &payoff, 2e-2);
         let mut jx: Vec<usize> = (0..n).collect();
         jx.sort_by(|&j1, &j2| sol.strategy2[j2].partial_cmp(&sol.strategy2[j1]).unwrap());
         jx = jx.into_iter().take(6).take_while(|&j| sol.strategy2[j] > 0.01).collect();
@@ -488,7 +630,7 @@ impl Player for GreedyPlayer {
             writeln!(html, "</tr>").unwrap();
         }
         writeln!(html, "</table>").unwrap();
-        writeln!(html, "<p>Game value: {:.1}</p>", sol.game_value).unwrap();
+        writeln!(html, "<p>Game value: {:.1} (duality gap after pruning {:.2})</p>", sol.game_value, sol.duality_gap).unwrap();
 
         write!(self.summary, " {:>5.1}s", timer.elapsed().as_secs_f64()).unwrap();
         append_to_summary!(html, "<td class=numcol>{:.1}s</td>", timer.elapsed().as_secs_f64());
@@ -499,6 +641,65 @@ impl Player for GreedyPlayer {
             .filter(|&(_, p)| p >= 2e-2)
             .collect()
     }
+}
+
+impl Player for GreedyPlayer {
+    fn begin(&mut self, _html: &mut dyn Write) {}
+
+    fn handle_opponent_move(&mut self,
+        capture: Option<(Square, Piece)>,
+        infoset: &Infoset,
+        html: &mut dyn Write,
+    ) {
+        assert_eq!(self.color, infoset.fog_state.side_to_play());
+        info!("opp capture: {:?}", capture);
+        info!("{} possible states", infoset.possible_states.len());
+        self.observation.push(Inflet::OpponentCapture(capture.map(|c| c.0)));
+        if let Some((cs, piece)) = capture {
+            writeln!(html, "<p>Opponent captured {} at <b>{:?}</b>.</p>", piece.to_emoji(), cs).unwrap();
+            self.last_capture = Some(piece);
+        } else {
+            self.last_capture = None;
+        }
+    }
+
+    fn choose_sense(&mut self, remaining_time: f64, infoset: &Infoset, html: &mut dyn Write) -> Vec<(Square, f32)> {
+        let deadline = self.time.start(
+            Phase::Sense, remaining_time, self.move_number as usize, infoset.possible_states.len());
+        self.ctx.stop = deadline.hard_stop();
+        let d = self.choose_sense_within(remaining_time, &deadline, infoset, html);
+        self.time.finish(&deadline);
+        d
+    }
+
+    fn handle_sense(&mut self,
+        sense: Square, sense_result: &[(Square, Option<Piece>)],
+        infoset: &Infoset,
+        html: &mut dyn Write,
+    ) {
+        assert_eq!(self.color, infoset.fog_state.side_to_play());
+        self.last_sense_result = Some((sense, sense_result.to_owned()));
+        // The opening book starts at white's first move, the sense before
+        // it tells nothing anyway.
+        if self.move_number > 0 {
+            self.observation.push(Inflet::Sense(sense, sense_result.to_owned()));
+        }
+        info!("sense {:?} -> {:?}", sense, sense_result);
+        info!("{} possible states", infoset.possible_states.len());
+        write!(self.summary, " {:>5}", infoset.possible_states.len()).unwrap();
+        append_to_summary!(html, "<td class=numcol>{}</td>", infoset.possible_states.len());
+        write!(html, "<p>{}</p>", infoset.to_html()).unwrap();
+        html.flush().unwrap();
+    }
+
+    fn choose_move(&mut self, remaining_time: f64, infoset: &Infoset, html: &mut dyn Write) -> Vec<(Option<Move>, f32)> {
+        let deadline = self.time.start(
+            Phase::Move, remaining_time, self.move_number as usize, infoset.possible_states.len());
+        self.ctx.stop = deadline.hard_stop();
+        let d = self.choose_move_within(remaining_time, &deadline, infoset, html);
+        self.time.finish(&deadline);
+        d
+    }
 
     fn handle_move(&mut self,
         requested: Option<Move>, taken: Option<Move>,
@@ -511,6 +712,7 @@ impl Player for GreedyPlayer {
         info!("taken move :    {:?}", taken);
         info!("capture: {:?}", capture);
         info!("{} possible states after my move", infoset.possible_states.len());
+        self.observation.push(Inflet::Move { requested, taken, capture: capture.as_ref().map(|c| c.0) });
         writeln!(html, "<p>requested: {:?}</p>", requested).unwrap();
         writeln!(html, "<p>taken: {:?}.</p>", taken).unwrap();
         if let Some((cs, ref cp)) = capture {
@@ -528,68 +730,67 @@ impl Player for GreedyPlayer {
         self.move_number += 2;
     }
 
-    fn get_summary(&self) -> String {
-        String::from_utf8(self.summary.clone()).unwrap()
+    fn game_over(&mut self, outcome: &Outcome, html: &mut dyn Write) {
+        let result = match outcome.winner {
+            None if outcome.reason == "ERROR" => "aborted",
+            None => "draw",
+            Some(c) if c == self.color => "won",
+            Some(_) => "lost",
+        };
+        writeln!(html, "<p>Game over, {} by {}.</p>", result, outcome.reason).unwrap();
+        writeln!(self.summary, "{} by {}", result, outcome.reason).unwrap();
     }
-}
 
-pub struct Solution {
-    pub game_value: f32,
-    pub strategy1: Vec<f32>,
-    pub strategy2: Vec<f32>,
+    fn get_summary(&self) -> String {
+        format!("{}{}\n", String::from_utf8(self.summary.clone()).unwrap(), self.time.summary())
+    }
 }
 
-pub fn fictitious_play(m: usize, n: usize, a: &[f32], num_steps: i32) -> Solution {
-    let mut strategy1 = vec![0f32; m];
-    let mut strategy2 = vec![0f32; n];
-
-    let mut vals1 = vec![0f32; m];
-    let mut vals2 = vec![0f32; n];
-
-    for _ in 0..num_steps {
-        {
-            let mut best = 0;
-            for i in 1..m {
-                if vals1[i] > vals1[best] {
-                    best = i;
-                }
-            }
-            for j in 0..n {
-                vals2[j] += a[best * n + j];
-            }
-            strategy1[best] += 1.0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::Encoding;
+    use crate::rbc_xf::{State, MaterialEvaluator, DEFAULT_INFO_PENALTY};
+
+    #[test]
+    fn test_opening_book() {
+        let mut evaluator = MaterialEvaluator { info_penalty: DEFAULT_INFO_PENALTY };
+        let mut game = RbcGame::new(1 + 2, &mut evaluator, State::ChoosePositionBeforeMove(Color::Black), vec![
+            BoardState::initial(),
+        ]);
+        let enc = Encoding::new(&mut game);
+        let mut cfr = crate::cfr::Cfr::with_variant(&enc, crate::cfr::Variant::Linear);
+        for _ in 0..10 {
+            cfr.step(&enc);
         }
-        {
-            let mut best = 0;
-            for j in 1..n {
-                if vals2[j] < vals2[best] {
-                    best = j;
-                }
-            }
-            for i in 0..m {
-                vals1[i] += a[i * n + best];
-            }
-            strategy2[best] += 1.0;
+        let book = StrategyBook::from_average_strategy(&enc, &cfr.average_strategy(&enc));
+        let first_move = book.get(&vec![Inflet::MyColor(Color::White)]).unwrap().to_vec();
+
+        let ai = GreedyAi {
+            experiment: false,
+            cfr_variant: crate::cfr::Variant::Linear,
+            cfr_target_nash_conv: None,
+            cfr_parallel: false,
+        };
+        let mut player = ai.player(Color::White, 1, 0.0);
+        player.opening_book = Some(Box::leak(Box::new(book)));
+        let mut infoset = Infoset::new(Color::White);
+        let mut html = Vec::new();
+        let d = player.choose_move(900.0, &infoset, &mut html);
+        assert!(!d.is_empty());
+        for (m, p) in &d {
+            assert!(first_move.contains(&(Action::Move(*m), *p)), "{:?}", m);
         }
-    }
-
-    let inv = 1.0 / num_steps as f32;
-    for p in &mut strategy1 {
-        *p *= inv;
-    }
-    for p in &mut strategy2 {
-        *p *= inv;
-    }
-    let mut game_value = 0.0;
-    for i in 0..m {
-        for j in 0..n {
-            game_value += a[i * n + j] * strategy1[i] * strategy2[j];
-        }
-    }
-
-    Solution {
-        game_value,
-        strategy1,
-        strategy2,
+        assert!(String::from_utf8(html).unwrap().contains("from the opening book"));
+
+        // Past the end of the book.
+        let m = Move::from_uci("e2e4");
+        infoset.my_move(Some(m), Some(m), None);
+        player.handle_move(Some(m), Some(m), None, &infoset, &mut Vec::new());
+        assert_eq!(player.observation, vec![
+            Inflet::MyColor(Color::White),
+            Inflet::Move { requested: Some(m), taken: Some(m), capture: None },
+        ]);
+        assert!(player.book_strategy(|a| Some(a.clone())).is_none());
     }
 }
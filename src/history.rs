@@ -1,7 +1,10 @@
+#[cfg(feature = "heavy")]
+use std::collections::HashMap;
+#[cfg(feature = "heavy")]
 use crate::api::RawGameHistory;
 use crate::game::{Square, Color, Piece};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MoveHistory {
     pub sense: Option<Square>,
     pub sense_result: Vec<(Square, Option<Piece>)>,
@@ -12,7 +15,7 @@ pub struct MoveHistory {
     pub fen_after: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GameHistory {
     pub white_name: String,
     pub black_name: String,
@@ -21,33 +24,38 @@ pub struct GameHistory {
     pub moves: Vec<MoveHistory>,
 }
 
-impl From<RawGameHistory> for GameHistory {
-    fn from(h: RawGameHistory) -> GameHistory {
+// Fails if the sides' lists don't line up.
+#[cfg(feature = "heavy")]
+impl std::convert::TryFrom<RawGameHistory> for GameHistory {
+    type Error = String;
+
+    fn try_from(h: RawGameHistory) -> Result<GameHistory, String> {
+        fn side<'a, T>(m: &'a HashMap<String, Vec<T>>, what: &str, color: &str) -> Result<&'a [T], String> {
+            m.get(color).map(Vec::as_slice).ok_or_else(|| format!("no {}[{}]", what, color))
+        }
+        fn check(ok: bool, what: &str, color: &str) -> Result<(), String> {
+            if ok { Ok(()) } else { Err(format!("{}[{}] doesn't match taken_moves", what, color)) }
+        }
         fn eq_or_one_less(x: usize, y: usize) -> bool {
             x == y || x + 1 == y
         }
 
-        let white_moves = h.taken_moves["true"].len();
-        // maybe they resigned or timed out after sensing
-        assert!(eq_or_one_less(white_moves, h.senses["true"].len()));
-        assert!(eq_or_one_less(white_moves, h.sense_results["true"].len()));
-        assert_eq!(white_moves, h.requested_moves["true"].len());
-        assert_eq!(white_moves, h.taken_moves["true"].len());
-        assert_eq!(white_moves, h.capture_squares["true"].len());
-        assert_eq!(white_moves, h.fens_before_move["true"].len());
-        assert_eq!(white_moves, h.fens_after_move["true"].len());
-
-        let black_moves = h.taken_moves["false"].len();
-        // maybe they resigned or timed out after sensing
-        assert!(eq_or_one_less(black_moves, h.senses["false"].len()));
-        assert!(eq_or_one_less(black_moves, h.sense_results["false"].len()));
-        assert_eq!(black_moves, h.requested_moves["false"].len());
-        assert_eq!(black_moves, h.taken_moves["false"].len());
-        assert_eq!(black_moves, h.capture_squares["false"].len());
-        assert_eq!(black_moves, h.fens_before_move["false"].len());
-        assert_eq!(black_moves, h.fens_after_move["false"].len());
+        let mut num_moves = [0, 0];
+        for (n, color) in num_moves.iter_mut().zip(&["true", "false"]) {
+            *n = side(&h.taken_moves, "taken_moves", color)?.len();
+            // maybe they resigned or timed out after sensing
+            check(eq_or_one_less(*n, side(&h.senses, "senses", color)?.len()), "senses", color)?;
+            check(eq_or_one_less(*n, side(&h.sense_results, "sense_results", color)?.len()), "sense_results", color)?;
+            check(*n == side(&h.requested_moves, "requested_moves", color)?.len(), "requested_moves", color)?;
+            check(*n == side(&h.capture_squares, "capture_squares", color)?.len(), "capture_squares", color)?;
+            check(*n == side(&h.fens_before_move, "fens_before_move", color)?.len(), "fens_before_move", color)?;
+            check(*n == side(&h.fens_after_move, "fens_after_move", color)?.len(), "fens_after_move", color)?;
+        }
+        let [white_moves, black_moves] = num_moves;
+        if !eq_or_one_less(black_moves, white_moves) {
+            return Err(format!("{} white moves, {} black moves", white_moves, black_moves));
+        }
 
-        assert!(eq_or_one_less(black_moves, white_moves));
         let mut moves = Vec::new();
         for i in 0..white_moves + black_moves {
             let color = if i % 2 == 0 { "true" } else { "false" };
@@ -61,12 +69,12 @@ impl From<RawGameHistory> for GameHistory {
                 fen_after: h.fens_after_move[color][i / 2].clone(),
             });
         }
-        GameHistory {
+        Ok(GameHistory {
             white_name: h.white_name,
             black_name: h.black_name,
             winner_color: h.winner_color,
             win_reason: h.win_reason.0,
             moves,
-        }
+        })
     }
 }
@@ -1,5 +1,6 @@
 use std::io::Read;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use rusqlite::{Connection, params};
 use crate::history::GameHistory;
 
@@ -59,7 +60,7 @@ pub fn game_query_map_fn<S: std::hash::BuildHasher>(
     dec.read_to_string(&mut h).unwrap();
 
     let h: crate::api::GameHistoryResponse = serde_json::from_str(&h).unwrap();
-    let h: GameHistory = h.game_history.into();
+    let h = GameHistory::try_from(h.game_history).unwrap();
 
     Ok(Some((game_id, h)))
 }
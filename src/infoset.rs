@@ -1,5 +1,6 @@
 use crate::game::{Square, Color, Piece, Move, BoardState};
 
+#[derive(Clone)]
 pub struct Infoset {
     pub color: Color,
     pub fog_state: BoardState,
@@ -180,9 +181,10 @@ impl Infoset {
         }
         square_and_entropy.sort_by(|(_, e1), (_, e2)| e2.partial_cmp(e1).unwrap());
 
+        let fast_states: Vec<crate::fast::State> = states.iter().map(Into::into).collect();
         let mut sense_entries = Vec::<SenseEntry>::new();
         for (sq, entropy) in square_and_entropy {
-            let sr_by_state: Vec<u32> = states.iter().map(|s| s.sense_fingerprint(sq)).collect();
+            let sr_by_state: Vec<u32> = fast_states.iter().map(|s| s.sense_fingerprint(sq)).collect();
             if sense_entries
                 .iter()
                 .any(|prev| partition_dominates(&prev.states_by_sr, &sr_by_state)) {
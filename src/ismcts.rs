@@ -0,0 +1,379 @@
+// Single-observer information set Monte Carlo tree search
+// (Cowling, Powley, Whitehouse 2012) adapted to RBC.
+//
+// Every iteration samples a determinization from the possible states
+// and walks the tree along it. Tree nodes correspond to sequences of our
+// own observations, so statistics are shared between all determinizations
+// consistent with what we saw.
+//
+// Our decisions (sense, then move) have the same actions
+// in all determinizations. Opponent decisions are over the moves legal
+// in the sampled state, which vary between determinizations, so they are
+// selected with availability counts.
+// Opponent sensing is not modeled; the opponent effectively sees
+// the sampled state, which makes the search somewhat pessimistic.
+//
+// Leaf values come from eval::search() squashed to [0, 1].
+
+use std::io::Write;
+use log::info;
+use rand::prelude::*;
+use crate::game::{Square, Color, Piece, Move, BoardState};
+use crate::ai_interface::{Ai, Player};
+use crate::infoset::Infoset;
+use crate::time_manager::{TimeManager, Phase, Deadline};
+
+#[derive(Clone)]
+pub struct IsmctsAi {
+    pub max_iterations: usize,
+    pub seconds_per_decision: f64,
+    pub leaf_depth: i32,
+    pub exploration: f64,
+}
+
+impl Default for IsmctsAi {
+    fn default() -> IsmctsAi {
+        IsmctsAi {
+            max_iterations: 1_000_000,
+            seconds_per_decision: 2.0,
+            leaf_depth: 1,
+            exploration: 0.7,
+        }
+    }
+}
+
+impl Ai for IsmctsAi {
+    fn make_player(&self, color: Color, seed: u64, increment: f64) -> Box<dyn Player> {
+        let mut ctx = crate::eval::Ctx::new(BoardState::initial());
+        ctx.expensive_eval = true;
+        ctx.killer_moves = true;
+        ctx.history_heuristic = true;
+        Box::new(IsmctsPlayer {
+            params: self.clone(),
+            rng: StdRng::seed_from_u64(seed),
+            color,
+            ctx,
+            time: TimeManager::new(increment),
+            ply: match color {
+                Color::White => 0,
+                Color::Black => 1,
+            },
+            summary: Vec::new(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Action {
+    Sense(Square),
+    Move(Option<Move>),  // requested for us, taken for the opponent
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Observation {
+    Sense(u32),  // BoardState::sense_fingerprint()
+    MyMove(Option<Move>, Option<Square>),  // taken move, capture square
+    OpponentMove(Option<Square>),  // capture square
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum NodeKind {
+    MySense,
+    MyMove,
+    OpponentMove,
+}
+
+struct Edge {
+    action: Action,
+    visits: u32,
+    avail: u32,
+    value: f64,  // sum, from the point of view of the player acting at the node
+}
+
+struct Node {
+    kind: NodeKind,
+    edges: Vec<Edge>,
+    // The opponent's action is not observed, so for opponent nodes it's None.
+    children: fnv::FnvHashMap<(Option<Action>, Observation), usize>,
+}
+
+struct Tree {
+    color: Color,
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn new(color: Color, root_kind: NodeKind, s: &BoardState) -> Tree {
+        let mut tree = Tree { color, nodes: Vec::new() };
+        tree.add_node(root_kind, s);
+        tree
+    }
+
+    fn add_node(&mut self, kind: NodeKind, s: &BoardState) -> usize {
+        let actions = match kind {
+            NodeKind::MySense => {
+                let mut result = Vec::with_capacity(36);
+                for rank in 1..7 {
+                    for file in 1..7 {
+                        result.push(Action::Sense(Square(rank * 8 + file)));
+                    }
+                }
+                result
+            }
+            NodeKind::MyMove => {
+                let mut fog_state = s.clone();
+                fog_state.fog_of_war(self.color);
+                fog_state.all_sensible_requested_moves().into_iter().map(Action::Move).collect()
+            }
+            NodeKind::OpponentMove => Vec::new(),  // added as encountered
+        };
+        self.nodes.push(Node {
+            kind,
+            edges: actions.into_iter().map(|action| Edge { action, visits: 0, avail: 0, value: 0.0 }).collect(),
+            children: Default::default(),
+        });
+        self.nodes.len() - 1
+    }
+}
+
+struct IsmctsPlayer {
+    params: IsmctsAi,
+    rng: StdRng,
+    color: Color,
+    ctx: crate::eval::Ctx,
+    time: TimeManager,
+    ply: usize,
+    summary: Vec<u8>,
+}
+
+impl IsmctsPlayer {
+    // From our point of view.
+    fn leaf_value(&mut self, s: &BoardState) -> f64 {
+        if let Some(winner) = s.winner() {
+            return if winner == self.color { 1.0 } else { 0.0 };
+        }
+        self.ctx.reset(s.clone());
+        let mut score = crate::eval::search(self.params.leaf_depth, -10000, 10000, &mut self.ctx);
+        if s.side_to_play() != self.color {
+            score = -score;
+        }
+        1.0 / (1.0 + (-f64::from(score) / 400.0).exp())
+    }
+
+    fn select(&mut self, edges: &[Edge], available: impl Iterator<Item=usize>) -> usize {
+        let c = self.params.exploration;
+        let mut best = None;
+        let mut best_score = f64::NEG_INFINITY;
+        for i in available {
+            let e = &edges[i];
+            let score = if e.visits == 0 {
+                // untried actions first, in random order
+                1e9 + self.rng.gen::<f64>()
+            } else {
+                e.value / f64::from(e.visits) +
+                c * (f64::from(e.avail).ln() / f64::from(e.visits)).sqrt()
+            };
+            if score > best_score {
+                best_score = score;
+                best = Some(i);
+            }
+        }
+        best.unwrap()
+    }
+
+    fn iteration(&mut self, tree: &mut Tree, mut s: BoardState) {
+        let mut path: Vec<(usize, usize)> = Vec::new();
+        let mut node = 0;
+        let value = loop {
+            if s.winner().is_some() {
+                break self.leaf_value(&s);
+            }
+            let kind = tree.nodes[node].kind;
+            let (edge, key, next_kind) = match kind {
+                NodeKind::MySense => {
+                    let n = tree.nodes[node].edges.len();
+                    let edge = self.select(&tree.nodes[node].edges, 0..n);
+                    let sq = match tree.nodes[node].edges[edge].action {
+                        Action::Sense(sq) => sq,
+                        a => unreachable!("{:?}", a),
+                    };
+                    let obs = Observation::Sense(s.sense_fingerprint(sq));
+                    (edge, (Some(Action::Sense(sq)), obs), NodeKind::MyMove)
+                }
+                NodeKind::MyMove => {
+                    let n = tree.nodes[node].edges.len();
+                    let edge = self.select(&tree.nodes[node].edges, 0..n);
+                    let requested = match tree.nodes[node].edges[edge].action {
+                        Action::Move(m) => m,
+                        a => unreachable!("{:?}", a),
+                    };
+                    let taken = s.requested_to_taken(requested);
+                    let capture = s.make_move(taken);
+                    s.clear_irrelevant_en_passant_square();
+                    let obs = Observation::MyMove(taken, capture);
+                    (edge, (Some(Action::Move(requested)), obs), NodeKind::OpponentMove)
+                }
+                NodeKind::OpponentMove => {
+                    let moves = s.all_moves();
+                    let edges = &mut tree.nodes[node].edges;
+                    let mut available = Vec::with_capacity(moves.len());
+                    for &m in &moves {
+                        let i = match edges.iter().position(|e| e.action == Action::Move(m)) {
+                            Some(i) => i,
+                            None => {
+                                edges.push(Edge { action: Action::Move(m), visits: 0, avail: 0, value: 0.0 });
+                                edges.len() - 1
+                            }
+                        };
+                        edges[i].avail += 1;
+                        available.push(i);
+                    }
+                    let edge = self.select(&tree.nodes[node].edges, available.into_iter());
+                    let m = match tree.nodes[node].edges[edge].action {
+                        Action::Move(m) => m,
+                        a => unreachable!("{:?}", a),
+                    };
+                    let capture = s.make_move(m);
+                    s.clear_irrelevant_en_passant_square();
+                    (edge, (None, Observation::OpponentMove(capture)), NodeKind::MySense)
+                }
+            };
+            if kind != NodeKind::OpponentMove {
+                for e in &mut tree.nodes[node].edges {
+                    e.avail += 1;
+                }
+            }
+            path.push((node, edge));
+            match tree.nodes[node].children.get(&key) {
+                Some(&child) => node = child,
+                None => {
+                    let child = tree.add_node(next_kind, &s);
+                    tree.nodes[node].children.insert(key, child);
+                    break self.leaf_value(&s);
+                }
+            }
+        };
+        for (node, edge) in path {
+            let node = &mut tree.nodes[node];
+            let e = &mut node.edges[edge];
+            e.visits += 1;
+            e.value += match node.kind {
+                NodeKind::MySense | NodeKind::MyMove => value,
+                NodeKind::OpponentMove => 1.0 - value,
+            };
+        }
+    }
+
+    fn search(&mut self, root_kind: NodeKind, deadline: &Deadline, infoset: &Infoset, html: &mut dyn Write) -> Action {
+        let timer = std::time::Instant::now();
+        let time_budget = self.params.seconds_per_decision.min(deadline.soft);
+        let states = &infoset.possible_states;
+        let mut tree = Tree::new(self.color, root_kind, &states[0]);
+        let mut iterations = 0;
+        while iterations < self.params.max_iterations {
+            if iterations % 16 == 0 && timer.elapsed().as_secs_f64() > time_budget {
+                break;
+            }
+            let s = states.choose(&mut self.rng).unwrap().clone();
+            self.iteration(&mut tree, s);
+            iterations += 1;
+        }
+
+        let root = &tree.nodes[0];
+        let mut order: Vec<&Edge> = root.edges.iter().collect();
+        order.sort_by_key(|e| std::cmp::Reverse(e.visits));
+        info!("ismcts: {} iterations, {} nodes, {:.2}s", iterations, tree.nodes.len(), timer.elapsed().as_secs_f64());
+        writeln!(html, "<p>ISMCTS: {} iterations, {} nodes, {:.2}s</p>",
+            iterations, tree.nodes.len(), timer.elapsed().as_secs_f64()).unwrap();
+        writeln!(html, "<table>").unwrap();
+        for e in order.iter().take(8) {
+            writeln!(html, "<tr><td>{:?}</td><td class=numcol>{}</td><td class=numcol>{:.3}</td></tr>",
+                e.action, e.visits, e.value / f64::from(e.visits.max(1))).unwrap();
+        }
+        writeln!(html, "</table>").unwrap();
+        write!(self.summary, " {:>6}", iterations).unwrap();
+        order[0].action
+    }
+}
+
+impl Player for IsmctsPlayer {
+    fn begin(&mut self, _html: &mut dyn Write) {}
+
+    fn handle_opponent_move(&mut self,
+        _capture: Option<(Square, Piece)>,
+        _infoset: &Infoset,
+        _html: &mut dyn Write,
+    ) {}
+
+    fn choose_sense(&mut self, remaining_time: f64, infoset: &Infoset, html: &mut dyn Write) -> Vec<(Square, f32)> {
+        assert_eq!(self.color, infoset.fog_state.side_to_play());
+        writeln!(html, "<h4>Sense</h4>").unwrap();
+        write!(self.summary, "{:>6}", infoset.possible_states.len()).unwrap();
+        let deadline = self.time.start(Phase::Sense, remaining_time, self.ply, infoset.possible_states.len());
+        let action = self.search(NodeKind::MySense, &deadline, infoset, html);
+        self.time.finish(&deadline);
+        match action {
+            Action::Sense(sq) => vec![(sq, 1.0)],
+            a => unreachable!("{:?}", a),
+        }
+    }
+
+    fn handle_sense(&mut self,
+        _sense: Square, _sense_result: &[(Square, Option<Piece>)],
+        infoset: &Infoset,
+        _html: &mut dyn Write,
+    ) {
+        write!(self.summary, " {:>5}", infoset.possible_states.len()).unwrap();
+    }
+
+    fn choose_move(&mut self, remaining_time: f64, infoset: &Infoset, html: &mut dyn Write) -> Vec<(Option<Move>, f32)> {
+        assert_eq!(self.color, infoset.fog_state.side_to_play());
+        writeln!(html, "<h4>Move</h4>").unwrap();
+        let deadline = self.time.start(Phase::Move, remaining_time, self.ply, infoset.possible_states.len());
+        let action = self.search(NodeKind::MyMove, &deadline, infoset, html);
+        self.time.finish(&deadline);
+        match action {
+            Action::Move(m) => vec![(m, 1.0)],
+            a => unreachable!("{:?}", a),
+        }
+    }
+
+    fn handle_move(&mut self,
+        _requested: Option<Move>, _taken: Option<Move>,
+        _capture: Option<(Square, Vec<Piece>)>,
+        _infoset: &Infoset,
+        _html: &mut dyn Write,
+    ) {
+        self.ply += 2;
+        writeln!(self.summary).unwrap();
+    }
+
+    fn get_summary(&self) -> String {
+        format!("{}{}\n", String::from_utf8(self.summary.clone()).unwrap(), self.time.summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures_king() {
+        let b: BoardState = fen::BoardState::from_fen("k7/8/8/8/8/8/8/K6Q w - - 0 0").unwrap().into();
+        let mut fog_state = b.clone();
+        fog_state.fog_of_war(Color::White);
+        let infoset = Infoset {
+            color: Color::White,
+            fog_state,
+            possible_states: vec![b],
+        };
+        let ai = IsmctsAi {
+            max_iterations: 300,
+            seconds_per_decision: 1e9,
+            ..IsmctsAi::default()
+        };
+        let mut player = ai.make_player(Color::White, 42, 0.0);
+        let m = player.choose_move(1e9, &infoset, &mut std::io::sink());
+        assert_eq!(m, vec![(Some(Move::from_uci("h1a8")), 1.0)]);
+    }
+}
@@ -8,11 +8,24 @@ pub mod ai_interface;
 pub mod distr;
 pub mod eval;
 pub mod greedy;
+pub mod ismcts;
+pub mod session;
+pub mod time_manager;
+pub mod throttle;
+pub mod shutdown;
 #[cfg(feature = "heavy")] pub mod api;
-#[cfg(feature = "heavy")] pub mod history;
+pub mod history;
 #[cfg(feature = "heavy")] pub mod history_db;
 pub mod logger;
+pub mod notify;
+pub mod dashboard;
+pub mod opponents;
 pub mod stats;
 pub mod fast;
 pub mod cfr;
+pub mod mccfr;
+pub mod matrix_game;
+pub mod cfr_io;
+#[cfg(test)] mod cfr_benchmarks;
 pub mod rbc_xf;
+pub mod perft;
@@ -0,0 +1,316 @@
+// Two-player zero-sum matrix games. The payoff matrix `a` is m x n
+// in row-major order, the row player maximizes, the column player minimizes.
+//
+// Small games are solved exactly with the simplex method,
+// large ones approximately with regret matching.
+// Either way the result reports its duality gap.
+
+// Simplex is used when the smaller dimension is at most this.
+const SIMPLEX_MAX_SIZE: usize = 300;
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub game_value: f32,
+    pub strategy1: Vec<f32>,
+    pub strategy2: Vec<f32>,
+    // What the row player could get against strategy2 minus what
+    // the column player could hold strategy1 to. Zero at equilibrium,
+    // otherwise each strategy is at most this far from optimal.
+    pub duality_gap: f32,
+}
+
+impl Solution {
+    pub fn new(m: usize, n: usize, a: &[f32], strategy1: Vec<f32>, strategy2: Vec<f32>) -> Solution {
+        assert_eq!(a.len(), m * n);
+        assert_eq!(strategy1.len(), m);
+        assert_eq!(strategy2.len(), n);
+        let row_values = row_values(m, n, a, &strategy2);
+        let col_values = col_values(m, n, a, &strategy1);
+        let game_value = strategy1.iter().zip(&row_values).map(|(x, v)| x * v).sum();
+        let best_row = row_values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let best_col = col_values.iter().cloned().fold(f32::INFINITY, f32::min);
+        Solution {
+            game_value,
+            strategy1,
+            strategy2,
+            duality_gap: best_row - best_col,
+        }
+    }
+
+    // Drops actions played with probability below threshold and renormalizes.
+    // The gap of the result tells how much that cost.
+    pub fn pruned(&self, m: usize, n: usize, a: &[f32], threshold: f32) -> Solution {
+        Solution::new(m, n, a, prune(&self.strategy1, threshold), prune(&self.strategy2, threshold))
+    }
+}
+
+fn prune(strategy: &[f32], threshold: f32) -> Vec<f32> {
+    let mut result: Vec<f32> = strategy.iter().map(|&p| if p < threshold { 0.0 } else { p }).collect();
+    let s: f32 = result.iter().sum();
+    if s == 0.0 {
+        return strategy.to_vec();
+    }
+    for p in &mut result {
+        *p /= s;
+    }
+    result
+}
+
+// Payoff of each row against the column strategy.
+fn row_values(m: usize, n: usize, a: &[f32], strategy2: &[f32]) -> Vec<f32> {
+    (0..m).map(|i| a[i * n..(i + 1) * n].iter().zip(strategy2).map(|(x, y)| x * y).sum()).collect()
+}
+
+// Payoff of each column against the row strategy.
+fn col_values(m: usize, n: usize, a: &[f32], strategy1: &[f32]) -> Vec<f32> {
+    assert_eq!(strategy1.len(), m);
+    let mut result = vec![0.0; n];
+    for (i, &x) in strategy1.iter().enumerate() {
+        if x == 0.0 {
+            continue;
+        }
+        for (r, &v) in result.iter_mut().zip(&a[i * n..(i + 1) * n]) {
+            *r += x * v;
+        }
+    }
+    result
+}
+
+// Exact for small games, otherwise regret matching until the gap
+// is below target_gap (or it runs out of steps, check the result).
+pub fn solve(m: usize, n: usize, a: &[f32], target_gap: f32) -> Solution {
+    if m.min(n) <= SIMPLEX_MAX_SIZE {
+        simplex(m, n, a)
+    } else {
+        regret_matching(m, n, a, target_gap, 100_000)
+    }
+}
+
+pub fn simplex(m: usize, n: usize, a: &[f32]) -> Solution {
+    assert_eq!(a.len(), m * n);
+    assert!(m > 0 && n > 0);
+    // The tableau has a row per constraint, so put the smaller
+    // dimension there, by swapping the players if needed.
+    if m > n {
+        let mut t = vec![0.0; m * n];
+        for i in 0..m {
+            for j in 0..n {
+                t[j * m + i] = -a[i * n + j];
+            }
+        }
+        let sol = simplex(n, m, &t);
+        return Solution::new(m, n, a, sol.strategy2, sol.strategy1);
+    }
+
+    // Shift payoffs to be at least 1, so the game value V is positive.
+    // Then with w = strategy2 / V the column player's problem is
+    //   maximize sum(w) subject to a w <= 1, w >= 0,
+    // with sum(w) = 1 / V at the optimum. The dual variables
+    // (in the objective row under the slacks) are strategy1 / V.
+    let min = a.iter().cloned().fold(f32::INFINITY, f32::min) as f64;
+    let shift = 1.0 - min;
+    let width = n + m + 1;
+    let mut t = vec![0f64; (m + 1) * width];
+    for i in 0..m {
+        for j in 0..n {
+            t[i * width + j] = a[i * n + j] as f64 + shift;
+        }
+        t[i * width + n + i] = 1.0;
+        t[i * width + width - 1] = 1.0;
+    }
+    for j in 0..n {
+        t[m * width + j] = -1.0;
+    }
+    let mut basis: Vec<usize> = (n..n + m).collect();
+
+    const EPS: f64 = 1e-9;
+    // Dantzig's rule, falling back to Bland's rule (which can't cycle)
+    // if it takes suspiciously long.
+    let bland_after = 10 * (n + m);
+    for iteration in 0.. {
+        let obj = &t[m * width..(m + 1) * width - 1];
+        let entering = if iteration < bland_after {
+            let (j, &c) = obj.iter().enumerate().min_by(|x, y| x.1.partial_cmp(y.1).unwrap()).unwrap();
+            if c >= -EPS { None } else { Some(j) }
+        } else {
+            obj.iter().position(|&c| c < -EPS)
+        };
+        let col = match entering {
+            Some(col) => col,
+            None => break,
+        };
+        let mut leaving: Option<usize> = None;
+        for i in 0..m {
+            let x = t[i * width + col];
+            if x > EPS {
+                let ratio = t[i * width + width - 1] / x;
+                leaving = match leaving {
+                    Some(l) => {
+                        let best = t[l * width + width - 1] / t[l * width + col];
+                        if ratio < best - EPS || ratio <= best + EPS && basis[i] < basis[l] {
+                            Some(i)
+                        } else {
+                            Some(l)
+                        }
+                    }
+                    None => Some(i),
+                };
+            }
+        }
+        // Bounded, because the shifted payoffs are positive.
+        let row = leaving.unwrap();
+
+        let p = t[row * width + col];
+        for x in &mut t[row * width..(row + 1) * width] {
+            *x /= p;
+        }
+        let pivot_row = t[row * width..(row + 1) * width].to_vec();
+        for i in 0..=m {
+            if i == row {
+                continue;
+            }
+            let f = t[i * width + col];
+            if f != 0.0 {
+                for (x, &y) in t[i * width..(i + 1) * width].iter_mut().zip(&pivot_row) {
+                    *x -= f * y;
+                }
+            }
+        }
+        basis[row] = col;
+    }
+
+    let mut w = vec![0f64; n];
+    for (i, &b) in basis.iter().enumerate() {
+        if b < n {
+            w[b] = t[i * width + width - 1];
+        }
+    }
+    let u: Vec<f64> = (0..m).map(|i| t[m * width + n + i].max(0.0)).collect();
+    Solution::new(m, n, a, normalized(&u), normalized(&w))
+}
+
+fn normalized(xs: &[f64]) -> Vec<f32> {
+    let s: f64 = xs.iter().sum();
+    xs.iter().map(|&x| (x / s) as f32).collect()
+}
+
+// Regret matching+ with alternating updates and linearly weighted
+// averages, like CFR+ on a one-shot game. Stops once the average
+// strategies are within target_gap of equilibrium.
+pub fn regret_matching(m: usize, n: usize, a: &[f32], target_gap: f32, max_steps: usize) -> Solution {
+    assert_eq!(a.len(), m * n);
+    let mut regret1 = vec![0f32; m];
+    let mut regret2 = vec![0f32; n];
+    let mut avg1 = vec![0f32; m];
+    let mut avg2 = vec![0f32; n];
+    let mut x = vec![1.0 / m as f32; m];
+    let mut y = vec![1.0 / n as f32; n];
+    for step in 1..=max_steps {
+        current_strategy(&regret2, &mut y);
+        let u1 = row_values(m, n, a, &y);
+        let v1: f32 = x.iter().zip(&u1).map(|(p, u)| p * u).sum();
+        for (r, u) in regret1.iter_mut().zip(&u1) {
+            *r = (*r + u - v1).max(0.0);
+        }
+        current_strategy(&regret1, &mut x);
+        let u2 = col_values(m, n, a, &x);
+        let v2: f32 = y.iter().zip(&u2).map(|(p, u)| p * u).sum();
+        for (r, u) in regret2.iter_mut().zip(&u2) {
+            *r = (*r + v2 - u).max(0.0);
+        }
+
+        let w = step as f32;
+        for (s, p) in avg1.iter_mut().zip(&x) {
+            *s += w * p;
+        }
+        for (s, p) in avg2.iter_mut().zip(&y) {
+            *s += w * p;
+        }
+        if step.is_power_of_two() || step == max_steps {
+            let sol = Solution::new(m, n, a, normalized_f32(&avg1), normalized_f32(&avg2));
+            if sol.duality_gap <= target_gap || step == max_steps {
+                return sol;
+            }
+        }
+    }
+    unreachable!()
+}
+
+fn current_strategy(regret: &[f32], dst: &mut [f32]) {
+    let s: f32 = regret.iter().sum();
+    if s > 0.0 {
+        for (d, r) in dst.iter_mut().zip(regret) {
+            *d = r / s;
+        }
+    }
+    // otherwise keep the previous strategy, any is a best response
+}
+
+fn normalized_f32(xs: &[f32]) -> Vec<f32> {
+    let s: f32 = xs.iter().sum();
+    xs.iter().map(|&x| x / s).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    fn assert_close(xs: &[f32], ys: &[f32]) {
+        assert_eq!(xs.len(), ys.len());
+        for (x, y) in xs.iter().zip(ys) {
+            assert!((x - y).abs() < 1e-4, "{:?} {:?}", xs, ys);
+        }
+    }
+
+    #[test]
+    fn test_known_games() {
+        // rock paper scissors
+        let a = [0.0, -1.0, 1.0, 1.0, 0.0, -1.0, -1.0, 1.0, 0.0];
+        let sol = simplex(3, 3, &a);
+        assert!(sol.game_value.abs() < 1e-5);
+        assert!(sol.duality_gap < 1e-5);
+        assert_close(&sol.strategy1, &[1.0 / 3.0; 3]);
+        assert_close(&sol.strategy2, &[1.0 / 3.0; 3]);
+
+        // no saddle point, value 1/7, strategies (3/7, 4/7) and (2/7, 5/7)
+        let a = [3.0, -1.0, -2.0, 1.0];
+        let sol = simplex(2, 2, &a);
+        assert!((sol.game_value - 1.0 / 7.0).abs() < 1e-5);
+        assert_close(&sol.strategy1, &[3.0 / 7.0, 4.0 / 7.0]);
+        assert_close(&sol.strategy2, &[2.0 / 7.0, 5.0 / 7.0]);
+
+        // saddle point at (1, 0), the other rows and columns are dominated
+        let a = [1.0, 5.0, 2.0, 3.0, 4.0, 6.0, 0.0, 7.0, 1.0];
+        let sol = simplex(3, 3, &a);
+        assert!((sol.game_value - 3.0).abs() < 1e-5);
+        assert_close(&sol.strategy1, &[0.0, 1.0, 0.0]);
+        assert_close(&sol.strategy2, &[1.0, 0.0, 0.0]);
+
+        // more rows than columns
+        let a = [3.0, -2.0, -1.0, 1.0, -5.0, -5.0];
+        let sol = simplex(3, 2, &a);
+        assert!((sol.game_value - 1.0 / 7.0).abs() < 1e-5);
+        assert_close(&sol.strategy1, &[2.0 / 7.0, 5.0 / 7.0, 0.0]);
+        assert_close(&sol.strategy2, &[3.0 / 7.0, 4.0 / 7.0]);
+    }
+
+    #[test]
+    fn test_random_games() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for &(m, n) in &[(1, 1), (1, 7), (7, 1), (5, 5), (20, 300), (300, 20), (40, 40)] {
+            // integer payoffs make degenerate pivots likely
+            let a: Vec<f32> = (0..m * n).map(|_| rng.gen_range(-5, 5) as f32 * 100.0).collect();
+            let exact = simplex(m, n, &a);
+            assert!(exact.duality_gap < 1e-2, "{} {} {}", m, n, exact.duality_gap);
+            let approx = regret_matching(m, n, &a, 1.0, 100_000);
+            assert!(approx.duality_gap <= 1.0, "{} {} {}", m, n, approx.duality_gap);
+            assert!((approx.game_value - exact.game_value).abs() <= 1.0);
+
+            let pruned = exact.pruned(m, n, &a, 0.01);
+            assert!(pruned.strategy1.iter().all(|&p| p == 0.0 || p >= 0.01));
+            assert!((pruned.strategy1.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+            assert!(pruned.duality_gap >= exact.duality_gap - 1e-2);
+        }
+    }
+}
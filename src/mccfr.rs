@@ -0,0 +1,228 @@
+// Monte Carlo CFR (Lanctot et al. 2009) walking cfr::Game directly,
+// without building an Encoding. Only the nodes on sampled trajectories
+// are expanded, and regrets are stored by Game::Infoset.
+//
+// External sampling: all actions of the traversing player are explored,
+// actions of the opponent and chance are sampled.
+// Outcome sampling: a single trajectory per iteration, with
+// epsilon-exploration for the traversing player and importance weighting.
+//
+// Utilities are from the point of view of player 0, like in cfr.rs.
+
+use std::collections::HashMap;
+use rand::prelude::*;
+use crate::cfr::{NodeInfo, Game, Encoding, normalize_to};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    External,
+    Outcome { exploration: f32 },
+}
+
+#[derive(Debug)]
+struct Entry<Action> {
+    actions: Vec<Action>,
+    total_regret: Vec<f32>,
+    total_sigma: Vec<f32>,
+}
+
+pub struct Mccfr<G: Game> {
+    entries: fnv::FnvHashMap<G::Infoset, Entry<G::Action>>,
+    sampling: Sampling,
+    rng: StdRng,
+    pub iterations: u64,
+    pub nodes_visited: u64,
+}
+
+impl<G: Game> Mccfr<G> {
+    pub fn new(sampling: Sampling, seed: u64) -> Self {
+        Mccfr {
+            entries: Default::default(),
+            sampling,
+            rng: StdRng::seed_from_u64(seed),
+            iterations: 0,
+            nodes_visited: 0,
+        }
+    }
+
+    pub fn num_infosets(&self) -> usize {
+        self.entries.len()
+    }
+
+    // One traversal for each player.
+    pub fn step(&mut self, g: &mut G) {
+        self.iterations += 1;
+        let s = g.initial_state();
+        for player in 0..2 {
+            match self.sampling {
+                Sampling::External => {
+                    self.external(g, &s, player);
+                }
+                Sampling::Outcome { exploration } => {
+                    self.outcome(g, &s, player, exploration, [1.0, 1.0], 1.0);
+                }
+            }
+        }
+    }
+
+    // Average strategy of all infosets encountered so far.
+    pub fn get_strategy(&self) -> HashMap<G::Infoset, Vec<(G::Action, f32)>> {
+        self.entries.iter().map(|(infoset, e)| {
+            let mut sigma = vec![0.0; e.actions.len()];
+            normalize_to(&e.total_sigma, &mut sigma);
+            (infoset.clone(), e.actions.iter().cloned().zip(sigma).collect())
+        }).collect()
+    }
+
+    // Average strategy indexed like enc.infosets (uniform for infosets
+    // never encountered), for use with cfr::nash_conv().
+    pub fn average_strategy(&self, enc: &Encoding<G>) -> Vec<Vec<f32>> {
+        enc.infosets.iter().map(|inf| {
+            let mut sigma = vec![0.0; inf.actions.len()];
+            match self.entries.get(&inf.orig) {
+                Some(e) => {
+                    assert_eq!(e.actions, inf.actions);
+                    normalize_to(&e.total_sigma, &mut sigma);
+                }
+                None => normalize_to(&vec![0.0; inf.actions.len()], &mut sigma),
+            }
+            sigma
+        }).collect()
+    }
+
+    // Current strategy (regret matching) at the infoset, creating it if needed.
+    fn current_strategy(&mut self, infoset: &G::Infoset, actions: Vec<G::Action>) -> Vec<f32> {
+        let e = self.entries.entry(infoset.clone()).or_insert_with(|| {
+            let n = actions.len();
+            Entry {
+                actions,
+                total_regret: vec![0.0; n],
+                total_sigma: vec![0.0; n],
+            }
+        });
+        let mut sigma = vec![0.0; e.actions.len()];
+        normalize_to(&e.total_regret, &mut sigma);
+        sigma
+    }
+
+    // Returns the sampled utility for the traversing player.
+    fn external(&mut self, g: &mut G, s: &G::State, traverser: usize) -> f32 {
+        self.nodes_visited += 1;
+        match g.node_info(s) {
+            NodeInfo::Terminal(x) => if traverser == 0 { x } else { -x },
+            NodeInfo::Chance(probs) => {
+                let a = sample_chance(&probs, &mut self.rng);
+                let mut s2 = s.clone();
+                g.apply(&mut s2, &a);
+                self.external(g, &s2, traverser)
+            }
+            NodeInfo::Choice { player, infoset, actions } => {
+                let sigma = self.current_strategy(&infoset, actions);
+                if player == traverser {
+                    let mut values = Vec::with_capacity(sigma.len());
+                    for i in 0..sigma.len() {
+                        let mut s2 = s.clone();
+                        g.apply(&mut s2, &self.entries[&infoset].actions[i]);
+                        values.push(self.external(g, &s2, traverser));
+                    }
+                    let v: f32 = sigma.iter().zip(&values).map(|(s, v)| s * v).sum();
+                    let e = self.entries.get_mut(&infoset).unwrap();
+                    for (r, x) in e.total_regret.iter_mut().zip(&values) {
+                        *r += x - v;
+                    }
+                    v
+                } else {
+                    let i = sample(&sigma, &mut self.rng);
+                    let e = self.entries.get_mut(&infoset).unwrap();
+                    for (t, s) in e.total_sigma.iter_mut().zip(&sigma) {
+                        *t += s;
+                    }
+                    let mut s2 = s.clone();
+                    g.apply(&mut s2, &e.actions[i]);
+                    self.external(g, &s2, traverser)
+                }
+            }
+        }
+    }
+
+    // Returns the sampled utility for the traversing player divided by
+    // the probability of sampling the trajectory, and the probability
+    // of the rest of the trajectory under the current strategy.
+    fn outcome(
+        &mut self,
+        g: &mut G,
+        s: &G::State,
+        traverser: usize,
+        exploration: f32,
+        pi: [f32; 2],
+        sample_prob: f32,
+    ) -> (f32, f32) {
+        self.nodes_visited += 1;
+        match g.node_info(s) {
+            NodeInfo::Terminal(x) => {
+                let u = if traverser == 0 { x } else { -x };
+                (u / sample_prob, 1.0)
+            }
+            NodeInfo::Chance(probs) => {
+                // Sampled on-policy, so chance probabilities cancel out.
+                let a = sample_chance(&probs, &mut self.rng);
+                let mut s2 = s.clone();
+                g.apply(&mut s2, &a);
+                self.outcome(g, &s2, traverser, exploration, pi, sample_prob)
+            }
+            NodeInfo::Choice { player, infoset, actions } => {
+                let sigma = self.current_strategy(&infoset, actions);
+                let n = sigma.len();
+                let i = if player == traverser {
+                    let q: Vec<f32> = sigma.iter().map(|s| exploration / n as f32 + (1.0 - exploration) * s).collect();
+                    sample(&q, &mut self.rng)
+                } else {
+                    sample(&sigma, &mut self.rng)
+                };
+                let q = if player == traverser {
+                    exploration / n as f32 + (1.0 - exploration) * sigma[i]
+                } else {
+                    sigma[i]
+                };
+                let mut s2 = s.clone();
+                g.apply(&mut s2, &self.entries[&infoset].actions[i]);
+                let mut pp = pi;
+                pp[player] *= sigma[i];
+                let (u, tail) = self.outcome(g, &s2, traverser, exploration, pp, sample_prob * q);
+
+                let e = self.entries.get_mut(&infoset).unwrap();
+                if player == traverser {
+                    let w = u * pi[1 - player];
+                    for (j, r) in e.total_regret.iter_mut().enumerate() {
+                        if j == i {
+                            *r += w * tail * (1.0 - sigma[i]);
+                        } else {
+                            *r -= w * tail * sigma[i];
+                        }
+                    }
+                } else {
+                    for (t, s) in e.total_sigma.iter_mut().zip(&sigma) {
+                        *t += pi[player] / sample_prob * s;
+                    }
+                }
+                (u, tail * sigma[i])
+            }
+        }
+    }
+}
+
+fn sample_chance<A: Clone>(probs: &[(f32, A)], rng: &mut StdRng) -> A {
+    let d = rand::distributions::WeightedIndex::new(probs.iter().map(|&(p, _)| p)).unwrap();
+    probs[d.sample(rng)].1.clone()
+}
+
+fn sample(probs: &[f32], rng: &mut StdRng) -> usize {
+    let mut x: f32 = rng.gen();
+    for (i, &p) in probs.iter().enumerate() {
+        x -= p;
+        if x < 0.0 {
+            return i;
+        }
+    }
+    probs.iter().rposition(|&p| p > 0.0).unwrap()
+}
@@ -0,0 +1,252 @@
+// Notifications about what the client is up to, sent to configurable sinks.
+//
+// Configured with a spec like
+//     panic,api_error=webhook:https://example.com/hook; all=file:logs/events.txt
+// that is, entries separated by ';', each a comma separated list of event
+// kinds (or "all") and a sink:
+//     stdout
+//     file:<path>         appends a line per event
+//     webhook:<url>       POSTs {"event": ..., "message": ..., "time": ...}
+//     command:<program and arguments>    message is appended as the last
+//                         argument, the event kind is in $RBC_EVENT
+
+use std::io::Write;
+use log::error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Panic,
+    GameFinished,
+    // all game slots are free
+    SlotIdle,
+    ApiError,
+}
+
+pub const ALL_EVENT_KINDS: [EventKind; 4] =
+    [EventKind::Panic, EventKind::GameFinished, EventKind::SlotIdle, EventKind::ApiError];
+
+impl EventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Panic => "panic",
+            EventKind::GameFinished => "game_finished",
+            EventKind::SlotIdle => "slot_idle",
+            EventKind::ApiError => "api_error",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<EventKind> {
+        ALL_EVENT_KINDS.iter().cloned().find(|k| k.name() == s)
+    }
+}
+
+pub trait Notifier: Send + Sync {
+    fn notify(&self, kind: EventKind, message: &str) -> Result<(), String>;
+}
+
+pub struct StdoutNotifier;
+
+impl Notifier for StdoutNotifier {
+    fn notify(&self, kind: EventKind, message: &str) -> Result<(), String> {
+        println!("[{}] {}", kind.name(), message);
+        Ok(())
+    }
+}
+
+pub struct FileNotifier {
+    pub path: std::path::PathBuf,
+}
+
+impl Notifier for FileNotifier {
+    fn notify(&self, kind: EventKind, message: &str) -> Result<(), String> {
+        let mut f = std::fs::OpenOptions::new()
+            .create(true).append(true)
+            .open(&self.path)
+            .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        writeln!(f, "{} [{}] {}",
+            chrono::offset::Utc::now().format("%Y-%m-%d %H:%M:%S"), kind.name(), message)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(feature = "heavy")]
+pub struct WebhookNotifier {
+    pub url: String,
+}
+
+#[cfg(feature = "heavy")]
+impl Notifier for WebhookNotifier {
+    fn notify(&self, kind: EventKind, message: &str) -> Result<(), String> {
+        let payload = serde_json::json!({
+            "event": kind.name(),
+            "message": message,
+            "time": chrono::offset::Utc::now().to_rfc3339(),
+        });
+        let resp = minreq::post(self.url.as_str())
+            .with_header("Content-Type", "application/json")
+            .with_body(payload.to_string())
+            .with_timeout(10)
+            .send()
+            .map_err(|e| e.to_string())?;
+        if resp.status_code / 100 != 2 {
+            return Err(format!("{} {}", resp.status_code, resp.body));
+        }
+        Ok(())
+    }
+}
+
+pub struct CommandNotifier {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl Notifier for CommandNotifier {
+    fn notify(&self, kind: EventKind, message: &str) -> Result<(), String> {
+        let status = std::process::Command::new(&self.program)
+            .args(&self.args)
+            .arg(message)
+            .env("RBC_EVENT", kind.name())
+            .status()
+            .map_err(|e| format!("{}: {}", self.program, e))?;
+        if !status.success() {
+            return Err(format!("{}: {}", self.program, status));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Notifications {
+    sinks: Vec<(Vec<EventKind>, Box<dyn Notifier>)>,
+}
+
+impl Notifications {
+    pub fn add(&mut self, kinds: &[EventKind], notifier: Box<dyn Notifier>) {
+        self.sinks.push((kinds.to_vec(), notifier));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    // Failing sinks are logged, not to take the caller down with them.
+    pub fn notify(&self, kind: EventKind, message: &str) {
+        for (kinds, notifier) in &self.sinks {
+            if kinds.contains(&kind) {
+                if let Err(e) = notifier.notify(kind, message) {
+                    error!("notifying about {:?} failed: {}", kind, e);
+                }
+            }
+        }
+    }
+
+    // See the top of the file for the format.
+    pub fn from_spec(spec: &str) -> Result<Notifications, String> {
+        let mut result = Notifications::default();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let eq = entry.find('=').ok_or_else(|| format!("no '=' in {:?}", entry))?;
+            let (kinds, sink) = (&entry[..eq], &entry[eq + 1..]);
+            let mut parsed_kinds = Vec::new();
+            for k in kinds.split(',').map(str::trim) {
+                if k == "all" {
+                    parsed_kinds.extend_from_slice(&ALL_EVENT_KINDS);
+                } else {
+                    parsed_kinds.push(EventKind::from_name(k).ok_or_else(|| format!("unknown event {:?}", k))?);
+                }
+            }
+            let (sink_type, arg) = match sink.find(':') {
+                Some(i) => (&sink[..i], sink[i + 1..].trim()),
+                None => (sink, ""),
+            };
+            let notifier: Box<dyn Notifier> = match sink_type.trim() {
+                "stdout" => Box::new(StdoutNotifier),
+                "file" if !arg.is_empty() => Box::new(FileNotifier { path: arg.into() }),
+                #[cfg(feature = "heavy")]
+                "webhook" if !arg.is_empty() => Box::new(WebhookNotifier { url: arg.to_owned() }),
+                "command" if !arg.is_empty() => {
+                    let mut words = arg.split_whitespace().map(str::to_owned);
+                    Box::new(CommandNotifier {
+                        program: words.next().unwrap(),
+                        args: words.collect(),
+                    })
+                }
+                _ => return Err(format!("bad sink {:?}", sink)),
+            };
+            result.add(&parsed_kinds, notifier);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<(EventKind, String)>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, kind: EventKind, message: &str) -> Result<(), String> {
+            self.0.lock().unwrap().push((kind, message.to_owned()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_filtering_and_spec() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut n = Notifications::default();
+        n.add(&[EventKind::Panic, EventKind::ApiError], Box::new(Recorder(log.clone())));
+        n.notify(EventKind::GameFinished, "won");
+        n.notify(EventKind::Panic, "oops");
+        assert_eq!(*log.lock().unwrap(), vec![(EventKind::Panic, "oops".to_owned())]);
+
+        assert_eq!(Notifications::from_spec("").unwrap().sinks.len(), 0);
+        let n = Notifications::from_spec("all=stdout; panic,slot_idle=command:echo -n").unwrap();
+        assert_eq!(n.sinks[0].0.len(), ALL_EVENT_KINDS.len());
+        assert_eq!(n.sinks[1].0, vec![EventKind::Panic, EventKind::SlotIdle]);
+        assert!(Notifications::from_spec("oops=stdout").is_err());
+        assert!(Notifications::from_spec("panic=carrier_pigeon").is_err());
+        assert!(Notifications::from_spec("panic=file").is_err());
+    }
+
+    #[cfg(feature = "heavy")]
+    #[test]
+    fn test_webhook() {
+        use std::io::{BufRead, BufReader, Read};
+        // Just enough of an HTTP server to receive one request.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let lower = line.to_lowercase();
+                if let Some(v) = lower.strip_prefix("content-length:") {
+                    content_length = v.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            (request_line, String::from_utf8(body).unwrap())
+        });
+
+        let n = Notifications::from_spec(&format!("game_finished=webhook:{}", url)).unwrap();
+        n.notify(EventKind::GameFinished, "42: W");
+        let (request_line, body) = server.join().unwrap();
+        assert!(request_line.starts_with("POST /hook "), "{}", request_line);
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["event"], "game_finished");
+        assert_eq!(payload["message"], "42: W");
+    }
+}
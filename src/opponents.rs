@@ -0,0 +1,237 @@
+// Whom to challenge next.
+//
+// Opponents that recently timed out or broke games against us are
+// avoided for a while, the rest are drawn with a preference for those
+// we know little about or do badly against. Colors are balanced per opponent.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use rand::Rng;
+use crate::game::Color;
+
+#[derive(Default, Debug, Clone)]
+pub struct OpponentStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub as_white: u32,
+    pub as_black: u32,
+    // whether the game hung, newest last
+    recent: VecDeque<bool>,
+}
+
+impl OpponentStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+}
+
+pub struct OpponentPolicy {
+    // our own name on the server
+    pub me: String,
+    // if not empty, nobody else is challenged
+    pub allow: HashSet<String>,
+    pub deny: HashSet<String>,
+    // An opponent hangs if that many of the last `hang_window` games
+    // with them ended in their timeout or an error.
+    pub hang_window: usize,
+    pub max_hangs: usize,
+    stats: HashMap<String, OpponentStats>,
+}
+
+impl OpponentPolicy {
+    pub fn new(me: &str) -> OpponentPolicy {
+        OpponentPolicy {
+            me: me.to_owned(),
+            allow: HashSet::new(),
+            deny: HashSet::new(),
+            hang_window: 5,
+            max_hangs: 2,
+            stats: HashMap::new(),
+        }
+    }
+
+    pub fn stats(&self, opponent: &str) -> Option<&OpponentStats> {
+        self.stats.get(opponent)
+    }
+
+    fn push_recent(&mut self, opponent: &str, hung: bool) -> &mut OpponentStats {
+        let window = self.hang_window;
+        let s = self.stats.entry(opponent.to_owned()).or_default();
+        s.recent.push_back(hung);
+        while s.recent.len() > window {
+            s.recent.pop_front();
+        }
+        s
+    }
+
+    // A finished game. Win reasons are as reported by the server.
+    pub fn record(&mut self, opponent: &str, my_color: Color, winner: Option<Color>, win_reason: &str) {
+        let hung = winner == Some(my_color) && win_reason == "TIMEOUT";
+        let s = self.push_recent(opponent, hung);
+        match winner {
+            None => s.draws += 1,
+            Some(c) if c == my_color => s.wins += 1,
+            Some(_) => s.losses += 1,
+        }
+        match my_color {
+            Color::White => s.as_white += 1,
+            Color::Black => s.as_black += 1,
+        }
+    }
+
+    // A game that we couldn't finish properly.
+    pub fn record_error(&mut self, opponent: &str) {
+        self.push_recent(opponent, true);
+    }
+
+    pub fn is_hanging(&self, opponent: &str) -> bool {
+        self.stats.get(opponent).is_some_and(|s| {
+            s.recent.iter().filter(|&&hung| hung).count() >= self.max_hangs
+        })
+    }
+
+    pub fn is_eligible(&self, opponent: &str) -> bool {
+        opponent != self.me &&
+        !self.deny.contains(opponent) &&
+        (self.allow.is_empty() || self.allow.contains(opponent)) &&
+        !self.is_hanging(opponent)
+    }
+
+    // Higher is more interesting.
+    pub fn priority(&self, opponent: &str) -> f64 {
+        let s = self.stats.get(opponent).cloned().unwrap_or_default();
+        let games = f64::from(s.games());
+        let unexplored = 1.0 / (1.0 + games).sqrt();
+        // with a prior of one loss and one win
+        let loss_rate = (f64::from(s.losses) + 0.5 * f64::from(s.draws) + 1.0) / (games + 2.0);
+        unexplored + loss_rate
+    }
+
+    // The color we played less against them.
+    pub fn color_for(&self, opponent: &str, rng: &mut impl Rng) -> Color {
+        let s = self.stats.get(opponent).cloned().unwrap_or_default();
+        if s.as_white < s.as_black {
+            Color::White
+        } else if s.as_black < s.as_white {
+            Color::Black
+        } else if rng.gen_bool(0.5) {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    pub fn choose(&self, users: &[String], rng: &mut impl Rng) -> Option<(String, Color)> {
+        let candidates: Vec<(&String, f64)> = users.iter()
+            .filter(|u| self.is_eligible(u))
+            .map(|u| (u, self.priority(u)))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let opponent = crate::distr::draw(
+            &candidates.iter().map(|&(u, p)| (u, p as f32)).collect::<Vec<_>>(), rng).to_string();
+        let color = self.color_for(&opponent, rng);
+        Some((opponent, color))
+    }
+
+    // Our most recent games from the downloaded game log, see history_db.
+    #[cfg(feature = "heavy")]
+    pub fn load_history(&mut self, conn: &rusqlite::Connection, max_games: usize) -> rusqlite::Result<usize> {
+        let mut q = conn.prepare("
+            SELECT white_name, black_name, winner_color, win_reason
+            FROM game
+            WHERE white_name = ?1 OR black_name = ?1
+            ORDER BY game_id DESC
+            LIMIT ?2")?;
+        // white, black, winner color, win reason
+        type Row = (String, String, Option<String>, Option<String>);
+        let rows = q.query_map(rusqlite::params![self.me, max_games as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?.collect::<rusqlite::Result<Vec<Row>>>()?;
+        // oldest first, so that the recent ones are the last
+        for (white_name, black_name, winner_color, win_reason) in rows.iter().rev() {
+            let win_reason = win_reason.as_deref().unwrap_or("");
+            let winner = match winner_color.as_deref() {
+                Some("White") => Some(Color::White),
+                Some("Black") => Some(Color::Black),
+                _ => None,
+            };
+            if *white_name == self.me {
+                self.record(black_name, Color::White, winner, win_reason);
+            } else {
+                self.record(white_name, Color::Black, winner, win_reason);
+            }
+        }
+        Ok(rows.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    #[test]
+    fn test_policy() {
+        let mut p = OpponentPolicy::new("me");
+        p.deny.insert("rude".to_owned());
+        let users: Vec<String> = ["me", "rude", "sleepy", "strong", "weak", "new"]
+            .iter().map(|s| s.to_string()).collect();
+
+        p.record("sleepy", Color::White, Some(Color::White), "TIMEOUT");
+        assert!(!p.is_hanging("sleepy"));
+        p.record_error("sleepy");
+        assert!(p.is_hanging("sleepy"));
+        for _ in 0..5 {
+            p.record("sleepy", Color::Black, Some(Color::Black), "KING_CAPTURE");
+        }
+        assert!(!p.is_hanging("sleepy"), "forgiven after a while");
+        p.record_error("sleepy");
+        p.record_error("sleepy");
+
+        for _ in 0..10 {
+            p.record("strong", Color::White, Some(Color::Black), "KING_CAPTURE");
+            p.record("weak", Color::White, Some(Color::White), "KING_CAPTURE");
+        }
+        assert!(p.priority("new") > p.priority("weak"));
+        assert!(p.priority("strong") > p.priority("weak"));
+        // we were always white against them
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(p.color_for("strong", &mut rng), Color::Black);
+
+        let mut cnt: HashMap<String, i32> = HashMap::new();
+        for _ in 0..1000 {
+            let (o, _) = p.choose(&users, &mut rng).unwrap();
+            *cnt.entry(o).or_default() += 1;
+        }
+        assert_eq!(cnt.len(), 3, "{:?}", cnt);
+        assert!(cnt["new"] > cnt["weak"] && cnt["strong"] > cnt["weak"], "{:?}", cnt);
+
+        p.allow.insert("weak".to_owned());
+        assert_eq!(p.choose(&users, &mut rng).unwrap().0, "weak");
+        p.allow.insert("sleepy".to_owned());
+        p.allow.remove("weak");
+        assert_eq!(p.choose(&users, &mut rng), None);
+    }
+
+    #[cfg(feature = "heavy")]
+    #[test]
+    fn test_load_history() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::history_db::init_tables(&conn);
+        conn.execute_batch("
+            INSERT INTO game (game_id, white_name, black_name, winner_color, win_reason) VALUES
+            (1, 'me', 'sleepy', 'White', 'TIMEOUT'),
+            (2, 'strong', 'me', 'White', 'KING_CAPTURE'),
+            (3, 'me', 'strong', NULL, NULL),
+            (4, 'other', 'strong', 'White', 'KING_CAPTURE');
+        ").unwrap();
+        let mut p = OpponentPolicy::new("me");
+        assert_eq!(p.load_history(&conn, 10).unwrap(), 3);
+        let s = p.stats("strong").unwrap();
+        assert_eq!((s.wins, s.losses, s.draws, s.as_white, s.as_black), (0, 1, 1, 1, 1));
+        assert_eq!(p.stats("sleepy").unwrap().wins, 1);
+        assert!(p.stats("other").is_none());
+    }
+}
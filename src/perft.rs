@@ -0,0 +1,164 @@
+// Move generator node counts under RBC rules:
+// no check legality, the game ends when a king is captured,
+// pass is a legal move, castling only requires empty squares
+// (it is allowed out of, through, and into attacked squares).
+//
+// A position without one of the kings is terminal,
+// so it contributes nothing to the counts at depth >= 1.
+
+use crate::game::{Move, BoardState};
+use crate::fast::{State, UndoEntry};
+
+pub const POSITIONS: &str = include_str!("perft_positions.txt");
+
+pub fn perft_naive(board: &BoardState, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if board.winner().is_some() {
+        return 0;
+    }
+    if depth == 1 {
+        return board.all_moves().len() as u64;
+    }
+    board.all_moves().into_iter().map(|m| {
+        let mut b = board.clone();
+        b.make_move(m);
+        perft_naive(&b, depth - 1)
+    }).sum()
+}
+
+pub fn perft_fast(state: &mut State, undo_log: &mut Vec<UndoEntry>, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if state.find_king(crate::game::Color::White).is_none() ||
+       state.find_king(crate::game::Color::Black).is_none() {
+        return 0;
+    }
+    let mut moves = vec![crate::fast::Move::null()];
+    state.all_moves(&mut moves);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut result = 0;
+    for m in moves {
+        state.make_move(m, undo_log);
+        result += perft_fast(state, undo_log, depth - 1);
+        state.unmake_move(m, undo_log);
+    }
+    result
+}
+
+// Per-move node counts at the root, in the order of BoardState::all_moves().
+pub fn divide(board: &BoardState, depth: u32) -> Vec<(Option<Move>, u64)> {
+    assert!(depth >= 1);
+    let mut state: State = board.into();
+    let mut undo_log = Vec::new();
+    board.all_moves().into_iter().map(|m| {
+        let fm = crate::fast::Move::from_simple_move(m, &state).unwrap();
+        state.make_move(fm, &mut undo_log);
+        let cnt = perft_fast(&mut state, &mut undo_log, depth - 1);
+        state.unmake_move(fm, &mut undo_log);
+        (m, cnt)
+    }).collect()
+}
+
+// Walks the tree to the given depth and checks at every node that
+// the fast generator, BoardState::all_moves() and all_moves_naive() agree
+// (see fast::verify() for the details of what is compared).
+// Panics on the first mismatch, after logging the path that led to it.
+pub fn cross_check(board: &BoardState, depth: u32) {
+    let mut path = Vec::new();
+    cross_check_rec(board, depth, &mut path);
+}
+
+fn cross_check_rec(board: &BoardState, depth: u32, path: &mut Vec<Option<Move>>) {
+    if board.winner().is_some() {
+        return;
+    }
+    let res = std::panic::catch_unwind(|| {
+        crate::fast::verify(board.clone());
+        let all_moves: fnv::FnvHashSet<_> = board.all_moves().into_iter().collect();
+        let all_moves_naive: fnv::FnvHashSet<_> = board.all_moves_naive().into_iter().collect();
+        assert_eq!(all_moves, all_moves_naive);
+    });
+    if let Err(e) = res {
+        log::error!("generators disagree after {:?}", path);
+        log::error!("{:#?}", board.render());
+        std::panic::resume_unwind(e);
+    }
+    if depth == 0 {
+        return;
+    }
+    for m in board.all_moves() {
+        let mut b = board.clone();
+        b.make_move(m);
+        b.clear_irrelevant_en_passant_square();
+        path.push(m);
+        cross_check_rec(&b, depth - 1, path);
+        path.pop();
+    }
+}
+
+pub struct Fixture {
+    pub fen: String,
+    pub counts: Vec<u64>,  // counts[i] is perft at depth i + 1
+}
+
+pub fn parse_fixtures(s: &str) -> Vec<Fixture> {
+    s.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut it = line.split(';');
+            let fen = it.next().unwrap().trim().to_owned();
+            let counts = it.next().unwrap()
+                .split_whitespace()
+                .map(|c| c.parse().unwrap())
+                .collect();
+            assert!(it.next().is_none(), "{:?}", line);
+            Fixture { fen, counts }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> BoardState {
+        fen::BoardState::from_fen(fen).unwrap().into()
+    }
+
+    #[test]
+    fn test_fixtures() {
+        for f in parse_fixtures(POSITIONS) {
+            let b = board(&f.fen);
+            let mut state: State = (&b).into();
+            let mut undo_log = Vec::new();
+            for (i, &expected) in f.counts.iter().enumerate().take(3) {
+                let depth = i as u32 + 1;
+                assert_eq!(perft_fast(&mut state, &mut undo_log, depth), expected, "{} depth {}", f.fen, depth);
+                if depth <= 2 {
+                    assert_eq!(perft_naive(&b, depth), expected, "{} depth {}", f.fen, depth);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cross_check() {
+        for f in parse_fixtures(POSITIONS) {
+            cross_check(&board(&f.fen), 1);
+        }
+    }
+
+    #[test]
+    fn test_divide() {
+        let b = BoardState::initial();
+        let d = divide(&b, 2);
+        assert_eq!(d.len(), 21);  // 20 moves and a pass
+        assert_eq!(d.iter().map(|&(_, c)| c).sum::<u64>(), perft_naive(&b, 2));
+    }
+}
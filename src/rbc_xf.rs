@@ -22,11 +22,92 @@ pub enum Inflet {
     }
 }
 
-pub struct RbcGame<'a> {
+// Value (for white) of a position where the depth-limited game is cut off.
+pub trait LeafEvaluator {
+    fn evaluate(&mut self, board: &BoardState, infoset: &[Infoset; 2]) -> f32;
+}
+
+pub const DEFAULT_INFO_PENALTY: f32 = 5.0;
+
+// Rewards knowing more about the position than the opponent does,
+// `info_penalty` points per bit.
+fn info_term(info_penalty: f32, infoset: &[Infoset; 2]) -> f32 {
+    info_penalty * (
+        -(infoset[0].possible_states.len() as f32).log2()
+        +(infoset[1].possible_states.len() as f32).log2())
+}
+
+// Alpha-beta search of the actual position, with iterative deepening.
+pub struct SearchEvaluator<'a> {
     ctx: &'a mut crate::eval::Ctx,
-    pub eval_cache: fnv::FnvHashMap<BoardState, i32>,
-    depth: usize,
     search_depth: i32,
+    pub info_penalty: f32,
+    pub cache: fnv::FnvHashMap<BoardState, i32>,
+}
+
+impl<'a> SearchEvaluator<'a> {
+    pub fn new(ctx: &'a mut crate::eval::Ctx, search_depth: i32) -> Self {
+        SearchEvaluator {
+            ctx,
+            search_depth,
+            info_penalty: DEFAULT_INFO_PENALTY,
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<'a> LeafEvaluator for SearchEvaluator<'a> {
+    fn evaluate(&mut self, board: &BoardState, infoset: &[Infoset; 2]) -> f32 {
+        let ctx = &mut self.ctx;
+        let search_depth = self.search_depth;
+        let e = self.cache.entry(board.clone()).or_insert_with(|| {
+            // Once ctx.stop is set, only material counts, to finish the tree quickly.
+            if !ctx.stop.load(std::sync::atomic::Ordering::Relaxed) {
+                ctx.reset(board.clone());
+                for d in 1..search_depth {
+                    crate::eval::search(d, -10000, 10000, ctx);
+                }
+                let score = crate::eval::search(search_depth, -10000, 10000, ctx);
+                if !ctx.aborted() {
+                    return score;
+                }
+            }
+            material_balance(board) * (1 - 2 * (board.side_to_play() as i32))
+        });
+        let score = *e * (1 - 2 * (board.side_to_play() as i32));
+        score as f32 + info_term(self.info_penalty, infoset)
+    }
+}
+
+// Material balance of the actual position, no search.
+pub struct MaterialEvaluator {
+    pub info_penalty: f32,
+}
+
+impl LeafEvaluator for MaterialEvaluator {
+    fn evaluate(&mut self, board: &BoardState, infoset: &[Infoset; 2]) -> f32 {
+        material_balance(board) as f32 + info_term(self.info_penalty, infoset)
+    }
+}
+
+// For white.
+fn material_balance(board: &BoardState) -> i32 {
+    let mut score = 0;
+    for sq in 0..64 {
+        if let Some(p) = board.get_piece(Square(sq)) {
+            let v = crate::eval::material_value(p.kind);
+            score += match p.color {
+                Color::White => v,
+                Color::Black => -v,
+            };
+        }
+    }
+    score
+}
+
+pub struct RbcGame<'a> {
+    evaluator: &'a mut dyn LeafEvaluator,
+    depth: usize,
     init_boards: Vec<BoardState>,
     init_state: State,
 }
@@ -34,16 +115,13 @@ pub struct RbcGame<'a> {
 impl<'a> RbcGame<'a> {
     pub fn new(
         depth: usize,
-        search_depth: i32,
-        ctx: &'a mut crate::eval::Ctx,
+        evaluator: &'a mut dyn LeafEvaluator,
         init_state: State,
         init_boards: Vec<BoardState>,
     ) -> Self {
         RbcGame {
             depth,
-            search_depth,
-            ctx,
-            eval_cache: Default::default(),
+            evaluator,
             init_boards,
             init_state,
         }
@@ -65,75 +143,91 @@ pub enum State {
     ChooseMove(Color),
 }
 
+// Everything node_info() needs, stepped forward by apply().
+#[derive(Clone)]
+pub struct Node {
+    board: BoardState,
+    infoset: [Infoset; 2],
+    observation: [Vec<Inflet>; 2],
+    state: State,
+    len: usize,  // number of actions applied so far
+}
+
 impl<'a> Game for RbcGame<'a> {
     type Action = Action;
     type Infoset = Vec<Inflet>;
+    type State = Node;
 
-    fn node_info(&mut self, h: &[Self::Action]) -> NodeInfo<Self::Action, Self::Infoset> {
-        let mut board = BoardState::initial();
-        let mut infoset = [Infoset::new(Color::White), Infoset::new(Color::Black)];
-        let mut observation = [vec![Inflet::MyColor(Color::White)], vec![Inflet::MyColor(Color::Black)]];
-        // let mut state = State::ChooseSense(Color::White);
-        // let mut state = State::ChoosePositionBeforeSense(Color::Black);
-        let mut state = self.init_state.clone();
+    fn initial_state(&mut self) -> Node {
+        Node {
+            board: BoardState::initial(),
+            infoset: [Infoset::new(Color::White), Infoset::new(Color::Black)],
+            observation: [vec![Inflet::MyColor(Color::White)], vec![Inflet::MyColor(Color::Black)]],
+            state: self.init_state.clone(),
+            len: 0,
+        }
+    }
 
-        for a in h {
-            match state {
-                State::ChooseSense(color) => {
-                    match a {
-                        &Action::Sense(sq) => {
-                            let sr = board.sense(sq);
-                            infoset[color as usize].sense(sq, &sr);
-                            observation[color as usize].push(Inflet::Sense(sq, sr));
-                            state = State::ChooseMove(color);
-                        }
-                        _ => unreachable!("{:?}", a),
+    fn apply(&mut self, node: &mut Node, a: &Action) {
+        node.len += 1;
+        match node.state {
+            State::ChooseSense(color) => {
+                match a {
+                    &Action::Sense(sq) => {
+                        let sr = node.board.sense(sq);
+                        node.infoset[color as usize].sense(sq, &sr);
+                        node.observation[color as usize].push(Inflet::Sense(sq, sr));
+                        node.state = State::ChooseMove(color);
                     }
+                    _ => unreachable!("{:?}", a),
                 }
-                State::ChooseMove(color) => {
-                    match a {
-                        &Action::Move(requested) => {
-                            let taken = board.requested_to_taken(requested);
-                            let capture = board.make_move(taken);
-                            infoset[color as usize].my_move(requested, taken, capture);
-                            observation[color as usize].push(Inflet::Move { requested, taken, capture });
-                            infoset[color.opposite() as usize].opponent_move(capture);
-                            observation[color.opposite() as usize].push(Inflet::OpponentCapture(capture));
-                            state = State::ChooseSense(color.opposite());
-                        }
-                        _ => unreachable!("{:?}", a),
+            }
+            State::ChooseMove(color) => {
+                match a {
+                    &Action::Move(requested) => {
+                        let taken = node.board.requested_to_taken(requested);
+                        let capture = node.board.make_move(taken);
+                        node.infoset[color as usize].my_move(requested, taken, capture);
+                        node.observation[color as usize].push(Inflet::Move { requested, taken, capture });
+                        node.infoset[color.opposite() as usize].opponent_move(capture);
+                        node.observation[color.opposite() as usize].push(Inflet::OpponentCapture(capture));
+                        node.state = State::ChooseSense(color.opposite());
                     }
+                    _ => unreachable!("{:?}", a),
                 }
-                State::ChoosePositionBeforeSense(color) |
-                State::ChoosePositionBeforeMove(color) => {
-                    match a {
-                        &Action::ChoosePosition(idx) => {
-                            board = self.init_boards[idx].clone();
-
-                            infoset[color as usize].fog_state = board.clone();
-                            infoset[color as usize].fog_state.fog_of_war(color);
-                            infoset[color as usize].possible_states = vec![board.clone()];
-                            observation[color as usize].push(Inflet::ChoosePosition(idx));
-
-                            let opp_inf = &mut infoset[color.opposite() as usize];
-                            opp_inf.fog_state = board.clone();
-                            opp_inf.fog_state.fog_of_war(color.opposite());
-                            opp_inf.possible_states = self.init_boards.clone();
-
-                            state = match state {
-                                State::ChoosePositionBeforeSense(_) => State::ChooseSense(color.opposite()),
-                                State::ChoosePositionBeforeMove(_) => State::ChooseMove(color.opposite()),
-                                _ => unreachable!(),
-                            };
-                        }
-                        _ => unreachable!("{:?}", a),
+            }
+            State::ChoosePositionBeforeSense(color) |
+            State::ChoosePositionBeforeMove(color) => {
+                match a {
+                    &Action::ChoosePosition(idx) => {
+                        node.board = self.init_boards[idx].clone();
+
+                        node.infoset[color as usize].fog_state = node.board.clone();
+                        node.infoset[color as usize].fog_state.fog_of_war(color);
+                        node.infoset[color as usize].possible_states = vec![node.board.clone()];
+                        node.observation[color as usize].push(Inflet::ChoosePosition(idx));
+
+                        let opp_inf = &mut node.infoset[color.opposite() as usize];
+                        opp_inf.fog_state = node.board.clone();
+                        opp_inf.fog_state.fog_of_war(color.opposite());
+                        opp_inf.possible_states = self.init_boards.clone();
+
+                        node.state = match node.state {
+                            State::ChoosePositionBeforeSense(_) => State::ChooseSense(color.opposite()),
+                            State::ChoosePositionBeforeMove(_) => State::ChooseMove(color.opposite()),
+                            _ => unreachable!(),
+                        };
                     }
+                    _ => unreachable!("{:?}", a),
                 }
             }
         }
+    }
 
-        if h.len() < self.depth && board.find_king(Color::White).is_some() && board.find_king(Color::Black).is_some() {
-            match state {
+    fn node_info(&mut self, node: &Node) -> NodeInfo<Self::Action, Self::Infoset> {
+        let Node { board, infoset, observation, state, len } = node;
+        if *len < self.depth && board.find_king(Color::White).is_some() && board.find_king(Color::Black).is_some() {
+            match *state {
                 State::ChooseSense(color) => {
                     let ss = infoset[color as usize].sensible_senses(&infoset[color as usize].possible_states);
                     return NodeInfo::Choice {
@@ -169,21 +263,40 @@ impl<'a> Game for RbcGame<'a> {
             }
         }
 
-        let ctx = &mut self.ctx;
-        let search_depth = self.search_depth;
-        let e = self.eval_cache.entry(board.clone()).or_insert_with(|| {
-            ctx.reset(board.clone());
-            for d in 1..search_depth {
-                crate::eval::search(d, -10000, 10000, ctx);
-            }
-            crate::eval::search(search_depth, -10000, 10000, ctx)
-        });
-        let score = *e * (1 - 2 * (board.side_to_play() as i32));
+        let score = self.evaluator.evaluate(board, infoset);
+        NodeInfo::Terminal(score)
+    }
+}
 
-        let score = score as f32 + 5.0 * (
-            -(infoset[0].possible_states.len() as f32).log2()
-            +(infoset[1].possible_states.len() as f32).log2());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::{Encoding, NodeRef};
 
-        NodeInfo::Terminal(score)
+    #[test]
+    fn test_same_tree_as_history_replay() {
+        let mut ctx = crate::eval::Ctx::new(BoardState::initial());
+        let mut evaluator = SearchEvaluator::new(&mut ctx, 2);
+        let mut game = RbcGame::new(1 + 3, &mut evaluator, State::ChoosePositionBeforeSense(Color::Black), vec![
+            BoardState::initial(),
+            fen::BoardState::from_fen("rnbqkb1r/pppppppp/8/8/8/5n2/PPPPPPPP/RNBQKBNR w KQkq - 0 0").unwrap().into(),
+        ]);
+        let enc = Encoding::new(&mut game);
+        let mut values: Vec<f64> = (0..enc.num_nodes() as u32)
+            .filter_map(|i| match enc.node(i) {
+                NodeRef::Terminal(v) => Some(f64::from(v)),
+                _ => None,
+            })
+            .collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // As computed before the switch to apply(), when node_info()
+        // replayed the whole history for every node.
+        assert_eq!(enc.num_nodes(), 266);
+        assert_eq!(enc.infosets.len(), 7);
+        assert_eq!(values.len(), 219);
+        assert_eq!(values[0], -9999.0);
+        assert_eq!(values[values.len() - 1], 350.0);
+        let sum: f64 = values.iter().sum();
+        assert!((sum - -896361.352).abs() < 0.01, "{}", sum);
     }
-}
\ No newline at end of file
+}
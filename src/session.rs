@@ -0,0 +1,755 @@
+// Turn protocol of a single game, as seen by one side.
+//
+// GameSession talks to the server through a Transport and turns
+// the request sequence (status polling, opponent move results, sense,
+// move, end turn) into a stream of events. Polling and backoff
+// on transient errors happen here, so whoever hosts a Player
+// only reacts to events and makes decisions.
+
+use std::io::Write;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::{info, error};
+use rand::Rng;
+use crate::game::{Square, Color, Piece, Move, BoardState};
+use crate::infoset::Infoset;
+use crate::history::{GameHistory, MoveHistory};
+use crate::ai_interface::Player;
+use crate::distr;
+
+#[derive(Debug)]
+pub enum TransportError {
+    // The request can't be served because the game has ended.
+    GameOver,
+    InvalidMove(String),
+    // Worth trying again later.
+    Transient(String),
+    Fatal(String),
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransportError::GameOver => write!(f, "game over"),
+            TransportError::InvalidMove(msg) => write!(f, "invalid move: {}", msg),
+            TransportError::Transient(msg) => write!(f, "transient: {}", msg),
+            TransportError::Fatal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+pub type TransportResult<T> = Result<T, TransportError>;
+
+pub type SenseResult = Vec<(Square, Option<Piece>)>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Status {
+    pub is_my_turn: bool,
+    pub is_over: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MoveResult {
+    pub requested: Option<Move>,
+    pub taken: Option<Move>,
+    pub capture_square: Option<Square>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub winner: Option<Color>,
+    pub reason: String,
+    // both sides' senses and moves, where the server tells
+    pub history: Option<GameHistory>,
+}
+
+// Requests of the turn protocol, one game, one side.
+pub trait Transport {
+    fn status(&mut self) -> TransportResult<Status>;
+    fn seconds_left(&mut self) -> TransportResult<f64>;
+    fn opponent_move_results(&mut self) -> TransportResult<Option<Square>>;
+    fn sense(&mut self, square: Square) -> TransportResult<SenseResult>;
+    fn make_move(&mut self, requested: Option<Move>) -> TransportResult<MoveResult>;
+    fn end_turn(&mut self) -> TransportResult<()>;
+    fn outcome(&mut self) -> TransportResult<Outcome>;
+    fn resign(&mut self) -> TransportResult<()>;
+}
+
+// How to go about our next turn, see GameSession::turn_policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurnPolicy {
+    // The player is told about this much of the clock.
+    Play { clock_fraction: f64 },
+    Resign,
+}
+
+#[derive(Debug)]
+pub enum Event {
+    // Not sent before white's first move.
+    OpponentMoved { capture: Option<(Square, Piece)> },
+    YourTurn { seconds_left: f64 },
+    GameOver(Outcome),
+}
+
+#[derive(Debug)]
+pub struct MoveOutcome {
+    pub requested: Option<Move>,
+    pub taken: Option<Move>,
+    // as observed, see Infoset::my_move()
+    pub capture: Option<(Square, Vec<Piece>)>,
+}
+
+pub struct GameSession<T: Transport> {
+    transport: T,
+    infoset: Infoset,
+    pub poll_interval: Duration,
+    pub max_backoff: Duration,
+    // how long a request is retried on transient errors before giving up,
+    // the only retries: transports try each request once
+    pub max_retry_time: Duration,
+    // asked at the start of each of our turns
+    pub turn_policy: Box<dyn FnMut() -> TurnPolicy + Send>,
+    ply: usize,
+    in_turn: bool,
+    over: bool,
+    finished: bool,
+    clock: f64,
+    turn_start: Instant,
+    pending: VecDeque<Event>,
+}
+
+// Ok(None) means the game is over.
+fn game_over_to_none<R>(r: TransportResult<R>) -> Result<Option<R>, String> {
+    match r {
+        Ok(x) => Ok(Some(x)),
+        Err(TransportError::GameOver) => Ok(None),
+        Err(TransportError::Transient(msg)) => Err(format!("giving up: {}", msg)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+impl<T: Transport> GameSession<T> {
+    pub fn new(transport: T, color: Color) -> GameSession<T> {
+        GameSession {
+            transport,
+            infoset: Infoset::new(color),
+            poll_interval: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+            max_retry_time: Duration::from_secs(60),
+            turn_policy: Box::new(|| TurnPolicy::Play { clock_fraction: 1.0 }),
+            ply: match color {
+                Color::White => 0,
+                Color::Black => 1,
+            },
+            in_turn: false,
+            over: false,
+            finished: false,
+            clock: 900.0,
+            turn_start: Instant::now(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn infoset(&self) -> &Infoset {
+        &self.infoset
+    }
+
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    // Halfmoves played so far, counting from white's first move.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    // Our clock, running during our turn.
+    pub fn seconds_left(&self) -> f64 {
+        if self.in_turn {
+            self.clock - self.turn_start.elapsed().as_secs_f64()
+        } else {
+            self.clock
+        }
+    }
+
+    fn call<R>(&mut self, f: impl Fn(&mut T) -> TransportResult<R>) -> TransportResult<R> {
+        let start = Instant::now();
+        let mut delay = Duration::from_secs(1).min(self.max_backoff);
+        loop {
+            match f(&mut self.transport) {
+                Err(TransportError::Transient(msg)) if start.elapsed() + delay <= self.max_retry_time => {
+                    error!("{}, retrying in {:?}", msg, delay);
+                    std::thread::sleep(delay);
+                    delay = (delay * 2).min(self.max_backoff);
+                }
+                r => return r,
+            }
+        }
+    }
+
+    // Blocks until something happens.
+    // After YourTurn, the turn must be finished with make_move().
+    pub fn next_event(&mut self) -> Result<Event, String> {
+        if let Some(e) = self.pending.pop_front() {
+            return Ok(e);
+        }
+        assert!(!self.in_turn, "finish the turn first");
+        if self.finished {
+            return Err("no events after game over".to_owned());
+        }
+        while !self.over {
+            let status = match game_over_to_none(self.call(|t| t.status()))? {
+                Some(status) => status,
+                None => break,
+            };
+            if status.is_over {
+                break;
+            }
+            if !status.is_my_turn {
+                std::thread::sleep(self.poll_interval);
+                continue;
+            }
+            let seconds_left = match game_over_to_none(self.call(|t| t.seconds_left()))? {
+                Some(t) => t,
+                None => break,
+            };
+            let capture_square = match game_over_to_none(self.call(|t| t.opponent_move_results()))? {
+                Some(cs) => cs,
+                None => break,
+            };
+            self.clock = seconds_left;
+            self.turn_start = Instant::now();
+            self.in_turn = true;
+            if self.ply > 0 {
+                let capture = self.infoset.opponent_move(capture_square);
+                self.pending.push_back(Event::OpponentMoved { capture });
+            } else if capture_square.is_some() {
+                return Err("capture before the first move".to_owned());
+            }
+            self.pending.push_back(Event::YourTurn { seconds_left });
+            return Ok(self.pending.pop_front().unwrap());
+        }
+        self.finished = true;
+        let outcome = game_over_to_none(self.call(|t| t.outcome()))?
+            .ok_or_else(|| "no outcome".to_owned())?;
+        info!("game over: {:?}", outcome);
+        Ok(Event::GameOver(outcome))
+    }
+
+    // Ok(None) if the game ended meanwhile, next_event() will tell how.
+    pub fn sense(&mut self, square: Square) -> Result<Option<SenseResult>, String> {
+        assert!(self.in_turn);
+        match game_over_to_none(self.call(|t| t.sense(square)))? {
+            Some(sense_result) => {
+                self.infoset.sense(square, &sense_result);
+                Ok(Some(sense_result))
+            }
+            None => {
+                self.end_of_game();
+                Ok(None)
+            }
+        }
+    }
+
+    // Also ends the turn. Passes if the move is rejected.
+    // Ok(None) if the game ended meanwhile, next_event() will tell how.
+    pub fn make_move(&mut self, requested: Option<Move>) -> Result<Option<MoveOutcome>, String> {
+        assert!(self.in_turn);
+        let mr = match self.call(|t| t.make_move(requested)) {
+            Err(TransportError::InvalidMove(msg)) => {
+                error!("{:?} rejected, passing instead: {}", requested, msg);
+                self.call(|t| t.make_move(None))
+            }
+            r => r,
+        };
+        let mr = match game_over_to_none(mr)? {
+            Some(mr) => mr,
+            None => {
+                self.end_of_game();
+                return Ok(None);
+            }
+        };
+        let capture = self.infoset.my_move(mr.requested, mr.taken, mr.capture_square);
+        self.ply += 2;
+        if game_over_to_none(self.call(|t| t.end_turn()))?.is_none() {
+            // Our move could have captured the king.
+            self.end_of_game();
+        } else {
+            self.clock = self.seconds_left();
+            self.in_turn = false;
+        }
+        Ok(Some(MoveOutcome {
+            requested: mr.requested,
+            taken: mr.taken,
+            capture,
+        }))
+    }
+
+    // Gives up, next_event() will tell how the game ended.
+    pub fn resign(&mut self) -> Result<(), String> {
+        assert!(self.in_turn);
+        game_over_to_none(self.call(|t| t.resign()))?;
+        self.end_of_game();
+        Ok(())
+    }
+
+    fn end_of_game(&mut self) {
+        self.clock = self.seconds_left();
+        self.in_turn = false;
+        self.over = true;
+    }
+}
+
+// Plays the whole game, drawing player's decisions from its distributions.
+pub fn host_player<T: Transport>(
+    session: &mut GameSession<T>,
+    player: &mut dyn Player,
+    rng: &mut impl Rng,
+    html: &mut dyn Write,
+) -> Result<Outcome, String> {
+    host_player_observed(session, player, rng, html, &mut |_| {})
+}
+
+// Same, calling `observe` whenever the clock or the infoset changed.
+// If the session fails, the player is told about an "ERROR" without a winner.
+pub fn host_player_observed<T: Transport>(
+    session: &mut GameSession<T>,
+    player: &mut dyn Player,
+    rng: &mut impl Rng,
+    html: &mut dyn Write,
+    observe: &mut dyn FnMut(&GameSession<T>),
+) -> Result<Outcome, String> {
+    player.begin(html);
+    let result = play_until_over(session, player, rng, html, observe);
+    match &result {
+        Ok(outcome) => player.game_over(outcome, html),
+        Err(msg) => {
+            writeln!(html, "<p>error: {}</p>", msg).unwrap();
+            player.game_over(&Outcome { winner: None, reason: "ERROR".to_owned(), history: None }, html);
+        }
+    }
+    result
+}
+
+fn play_until_over<T: Transport>(
+    session: &mut GameSession<T>,
+    player: &mut dyn Player,
+    rng: &mut impl Rng,
+    html: &mut dyn Write,
+    observe: &mut dyn FnMut(&GameSession<T>),
+) -> Result<Outcome, String> {
+    loop {
+        let event = session.next_event()?;
+        observe(session);
+        match event {
+            Event::OpponentMoved { capture } => {
+                player.handle_opponent_move(capture, session.infoset(), html);
+            }
+            Event::YourTurn { .. } => {
+                writeln!(html, "<hr>").unwrap();
+                let clock_fraction = match (session.turn_policy)() {
+                    TurnPolicy::Play { clock_fraction } => clock_fraction,
+                    TurnPolicy::Resign => {
+                        info!("resigning");
+                        writeln!(html, "<p>resigning</p>").unwrap();
+                        session.resign()?;
+                        continue;
+                    }
+                };
+                if player.should_resign(session.infoset()) {
+                    info!("player resigns");
+                    writeln!(html, "<p>player resigns</p>").unwrap();
+                    session.resign()?;
+                    continue;
+                }
+                let sense_distr = player.choose_sense(
+                    session.seconds_left() * clock_fraction, session.infoset(), html);
+                let sense = *distr::draw(&sense_distr, rng);
+                writeln!(html, "<p>sense: {:?}</p>", sense_distr).unwrap();
+                writeln!(html, "<p>sense: {:?}</p>", sense).unwrap();
+                let sense_result = match session.sense(sense)? {
+                    Some(sr) => sr,
+                    None => continue,
+                };
+                observe(session);
+                player.handle_sense(sense, &sense_result, session.infoset(), html);
+
+                let requested_distr = player.choose_move(
+                    session.seconds_left() * clock_fraction, session.infoset(), html);
+                let requested = *distr::draw(&requested_distr, rng);
+                writeln!(html, "<p>requested: {:?}</p>", requested_distr).unwrap();
+                if let Some(mo) = session.make_move(requested)? {
+                    observe(session);
+                    player.handle_move(mo.requested, mo.taken, mo.capture, session.infoset(), html);
+                }
+            }
+            Event::GameOver(outcome) => return Ok(outcome),
+        }
+    }
+}
+
+// In-process stand-in for the game server, for tests and local matches.
+pub struct LocalServer {
+    board: BoardState,
+    // in the current turn
+    sense: Option<(Square, SenseResult)>,
+    moved: bool,
+    // for the GameHistory in the outcome
+    moves: Vec<MoveHistory>,
+    last_capture: Option<Square>,
+    clocks: [f64; 2],
+    turn_start: Instant,
+    outcome: Option<Outcome>,
+}
+
+fn color_index(c: Color) -> usize {
+    match c {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+impl LocalServer {
+    // Transports for white and black.
+    pub fn new_game(seconds: f64) -> (LocalTransport, LocalTransport) {
+        let server = Arc::new(Mutex::new(LocalServer {
+            board: BoardState::initial(),
+            sense: None,
+            moved: false,
+            moves: Vec::new(),
+            last_capture: None,
+            clocks: [seconds; 2],
+            turn_start: Instant::now(),
+            outcome: None,
+        }));
+        (
+            LocalTransport { server: server.clone(), color: Color::White },
+            LocalTransport { server, color: Color::Black },
+        )
+    }
+
+    fn seconds_left(&self, color: Color) -> f64 {
+        let mut t = self.clocks[color_index(color)];
+        if self.board.side_to_play() == color {
+            t -= self.turn_start.elapsed().as_secs_f64();
+        }
+        t
+    }
+
+    // There are no player names here.
+    fn finish(&mut self, winner: Color, reason: &str) {
+        self.outcome = Some(Outcome {
+            winner: Some(winner),
+            reason: reason.to_owned(),
+            history: Some(GameHistory {
+                white_name: "white".to_owned(),
+                black_name: "black".to_owned(),
+                winner_color: Some(winner),
+                win_reason: reason.to_owned(),
+                moves: self.moves.clone(),
+            }),
+        });
+    }
+
+    fn check_turn(&self, color: Color) -> TransportResult<()> {
+        if self.outcome.is_some() {
+            return Err(TransportError::GameOver);
+        }
+        // Sides switch in make_move(), but the turn only ends in end_turn().
+        if self.board.side_to_play() != color || self.moved {
+            return Err(TransportError::Fatal("not your turn".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+pub struct LocalTransport {
+    server: Arc<Mutex<LocalServer>>,
+    color: Color,
+}
+
+impl Transport for LocalTransport {
+    fn status(&mut self) -> TransportResult<Status> {
+        let s = self.server.lock().unwrap();
+        Ok(Status {
+            is_my_turn: s.check_turn(self.color).is_ok(),
+            is_over: s.outcome.is_some(),
+        })
+    }
+
+    fn seconds_left(&mut self) -> TransportResult<f64> {
+        Ok(self.server.lock().unwrap().seconds_left(self.color))
+    }
+
+    fn opponent_move_results(&mut self) -> TransportResult<Option<Square>> {
+        let s = self.server.lock().unwrap();
+        s.check_turn(self.color)?;
+        Ok(s.last_capture)
+    }
+
+    fn sense(&mut self, square: Square) -> TransportResult<SenseResult> {
+        let mut s = self.server.lock().unwrap();
+        s.check_turn(self.color)?;
+        if s.sense.is_some() {
+            return Err(TransportError::Fatal("already sensed".to_owned()));
+        }
+        let sense_result = s.board.sense(square);
+        s.sense = Some((square, sense_result.clone()));
+        Ok(sense_result)
+    }
+
+    fn make_move(&mut self, requested: Option<Move>) -> TransportResult<MoveResult> {
+        let mut s = self.server.lock().unwrap();
+        s.check_turn(self.color)?;
+        let mut fog_state = s.board.clone();
+        fog_state.fog_of_war(self.color);
+        if !fog_state.all_sensible_requested_moves().contains(&requested) {
+            return Err(TransportError::InvalidMove(format!("{:?}", requested)));
+        }
+        let taken = s.board.requested_to_taken(requested);
+        let fen_before = s.board.to_fen();
+        let capture_square = s.board.make_move(taken);
+        let (sense, sense_result) = match s.sense.clone() {
+            Some((square, sense_result)) => (Some(square), sense_result),
+            None => (None, Vec::new()),
+        };
+        let fen_after = s.board.to_fen();
+        s.moves.push(MoveHistory {
+            sense,
+            sense_result,
+            requested_move: requested.map(Move::to_uci),
+            taken_move: taken.map(Move::to_uci),
+            capture_square,
+            fen_before,
+            fen_after,
+        });
+        s.last_capture = capture_square;
+        s.moved = true;
+        Ok(MoveResult { requested, taken, capture_square })
+    }
+
+    fn end_turn(&mut self) -> TransportResult<()> {
+        let mut s = self.server.lock().unwrap();
+        if s.outcome.is_some() {
+            return Err(TransportError::GameOver);
+        }
+        // make_move() already switched sides
+        if !s.moved || s.board.side_to_play() == self.color {
+            return Err(TransportError::Fatal("not your turn".to_owned()));
+        }
+        let used = s.turn_start.elapsed().as_secs_f64();
+        s.clocks[color_index(self.color)] -= used;
+        s.turn_start = Instant::now();
+        s.sense = None;
+        s.moved = false;
+        if let Some(winner) = s.board.winner() {
+            s.finish(winner, "KING_CAPTURE");
+            return Err(TransportError::GameOver);
+        }
+        if s.clocks[color_index(self.color)] < 0.0 {
+            s.finish(self.color.opposite(), "TIMEOUT");
+            return Err(TransportError::GameOver);
+        }
+        Ok(())
+    }
+
+    fn outcome(&mut self) -> TransportResult<Outcome> {
+        match &self.server.lock().unwrap().outcome {
+            Some(outcome) => Ok(outcome.clone()),
+            None => Err(TransportError::Fatal("game is not over".to_owned())),
+        }
+    }
+
+    fn resign(&mut self) -> TransportResult<()> {
+        let mut s = self.server.lock().unwrap();
+        if s.outcome.is_some() {
+            return Err(TransportError::GameOver);
+        }
+        s.finish(self.color.opposite(), "RESIGN");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    // Random players would make infosets explode,
+    // so white goes for the king and black passes.
+    pub(crate) struct ScriptedPlayer {
+        sense: Square,
+        moves: Vec<Option<Move>>,
+        // once out of moves
+        resign: bool,
+        pub reason: Option<String>,
+    }
+
+    impl ScriptedPlayer {
+        pub(crate) fn new(sense: &str, moves: &[&str]) -> ScriptedPlayer {
+            ScriptedPlayer {
+                sense: Square::from_san(sense),
+                moves: moves.iter().map(|m| Some(Move::from_uci(m))).collect(),
+                resign: false,
+                reason: None,
+            }
+        }
+    }
+
+    impl Player for ScriptedPlayer {
+        fn begin(&mut self, _html: &mut dyn Write) {}
+        fn handle_opponent_move(&mut self, _: Option<(Square, Piece)>, _: &Infoset, _: &mut dyn Write) {}
+        fn choose_sense(&mut self, _: f64, _: &Infoset, _: &mut dyn Write) -> Vec<(Square, f32)> {
+            vec![(self.sense, 1.0)]
+        }
+        fn handle_sense(&mut self, _: Square, _: &[(Square, Option<Piece>)], _: &Infoset, _: &mut dyn Write) {}
+        fn choose_move(&mut self, _: f64, _: &Infoset, _: &mut dyn Write) -> Vec<(Option<Move>, f32)> {
+            vec![(if self.moves.is_empty() { None } else { self.moves.remove(0) }, 1.0)]
+        }
+        fn handle_move(&mut self, _: Option<Move>, _: Option<Move>, _: Option<(Square, Vec<Piece>)>, _: &Infoset, _: &mut dyn Write) {}
+        fn should_resign(&mut self, _: &Infoset) -> bool {
+            self.resign && self.moves.is_empty()
+        }
+        fn game_over(&mut self, outcome: &Outcome, _: &mut dyn Write) {
+            self.reason = Some(outcome.reason.clone());
+        }
+        fn get_summary(&self) -> String { String::new() }
+    }
+
+    // Plays both sides in threads, returns outcomes, plies and players.
+    fn play_local(white: ScriptedPlayer, black: ScriptedPlayer) -> Vec<(Outcome, usize, ScriptedPlayer)> {
+        let (white_transport, black_transport) = LocalServer::new_game(900.0);
+        let players = vec![
+            (white_transport, Color::White, white),
+            (black_transport, Color::Black, black),
+        ];
+        let threads: Vec<_> = players.into_iter()
+            .map(|(transport, color, mut player)| std::thread::spawn(move || {
+                let mut session = GameSession::new(transport, color);
+                session.poll_interval = Duration::from_millis(1);
+                let mut rng = StdRng::seed_from_u64(42);
+                let outcome = host_player(&mut session, &mut player, &mut rng, &mut std::io::sink()).unwrap();
+                (outcome, session.ply(), player)
+            }))
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_local_game() {
+        let results = play_local(
+            ScriptedPlayer::new("e7", &["e2e4", "d1h5", "h5f7", "f7e8"]),
+            ScriptedPlayer::new("e2", &[]));
+        for (outcome, _, player) in &results {
+            assert_eq!(outcome.winner, Some(Color::White));
+            assert_eq!(outcome.reason, "KING_CAPTURE");
+            assert_eq!(player.reason.as_deref(), Some("KING_CAPTURE"));
+        }
+        assert_eq!(results[0].1, 8);
+        assert_eq!(results[1].1, 7);
+
+        let history = results[0].0.history.as_ref().unwrap();
+        assert_eq!(history.winner_color, Some(Color::White));
+        assert_eq!(history.moves.len(), 7);
+        assert_eq!(history.moves[0].fen_before, crate::game::STARTING_FEN);
+        for (a, b) in history.moves.iter().zip(&history.moves[1..]) {
+            assert_eq!(a.fen_after, b.fen_before);
+        }
+        assert_eq!(history.moves[0].sense, Some(Square::from_san("e7")));
+        assert_eq!(history.moves[0].requested_move.as_deref(), Some("e2e4"));
+        assert_eq!(history.moves[1].taken_move, None);
+        assert_eq!(history.moves[6].capture_square, Some(Square::from_san("e8")));
+    }
+
+    struct BrokenTransport;
+
+    impl Transport for BrokenTransport {
+        fn status(&mut self) -> TransportResult<Status> { Err(TransportError::Fatal("broken".to_owned())) }
+        fn seconds_left(&mut self) -> TransportResult<f64> { unreachable!() }
+        fn opponent_move_results(&mut self) -> TransportResult<Option<Square>> { unreachable!() }
+        fn sense(&mut self, _: Square) -> TransportResult<SenseResult> { unreachable!() }
+        fn make_move(&mut self, _: Option<Move>) -> TransportResult<MoveResult> { unreachable!() }
+        fn end_turn(&mut self) -> TransportResult<()> { unreachable!() }
+        fn outcome(&mut self) -> TransportResult<Outcome> { unreachable!() }
+        fn resign(&mut self) -> TransportResult<()> { unreachable!() }
+    }
+
+    // Down for good.
+    struct FlakyTransport {
+        calls: usize,
+    }
+
+    impl Transport for FlakyTransport {
+        fn status(&mut self) -> TransportResult<Status> {
+            self.calls += 1;
+            Err(TransportError::Transient("503".to_owned()))
+        }
+        fn seconds_left(&mut self) -> TransportResult<f64> { unreachable!() }
+        fn opponent_move_results(&mut self) -> TransportResult<Option<Square>> { unreachable!() }
+        fn sense(&mut self, _: Square) -> TransportResult<SenseResult> { unreachable!() }
+        fn make_move(&mut self, _: Option<Move>) -> TransportResult<MoveResult> { unreachable!() }
+        fn end_turn(&mut self) -> TransportResult<()> { unreachable!() }
+        fn outcome(&mut self) -> TransportResult<Outcome> { unreachable!() }
+        fn resign(&mut self) -> TransportResult<()> { unreachable!() }
+    }
+
+    #[test]
+    fn test_retry_time() {
+        let mut session = GameSession::new(FlakyTransport { calls: 0 }, Color::White);
+        session.max_backoff = Duration::from_millis(10);
+        session.max_retry_time = Duration::from_millis(100);
+        let start = Instant::now();
+        let e = session.next_event().unwrap_err();
+        assert!(e.contains("giving up"), "{}", e);
+        assert!(start.elapsed() <= Duration::from_millis(100));
+        assert!(session.transport().calls > 5);
+    }
+
+    #[test]
+    fn test_player_told_about_error() {
+        let mut session = GameSession::new(BrokenTransport, Color::White);
+        let mut player = ScriptedPlayer::new("e7", &[]);
+        let mut rng = StdRng::seed_from_u64(42);
+        assert!(host_player(&mut session, &mut player, &mut rng, &mut std::io::sink()).is_err());
+        assert_eq!(player.reason.as_deref(), Some("ERROR"));
+    }
+
+    #[test]
+    fn test_player_resigns() {
+        let mut white = ScriptedPlayer::new("e7", &["e2e4"]);
+        white.resign = true;
+        let results = play_local(white, ScriptedPlayer::new("e2", &[]));
+        for (outcome, _, player) in &results {
+            assert_eq!(outcome.winner, Some(Color::Black));
+            assert_eq!(player.reason.as_deref(), Some("RESIGN"));
+        }
+        assert_eq!(results[0].1, 2);
+    }
+
+    #[test]
+    fn test_resign() {
+        let (white, black) = LocalServer::new_game(900.0);
+        let black = std::thread::spawn(move || {
+            let mut session = GameSession::new(black, Color::Black);
+            session.poll_interval = Duration::from_millis(1);
+            let mut player = ScriptedPlayer::new("e2", &[]);
+            let mut rng = StdRng::seed_from_u64(42);
+            host_player(&mut session, &mut player, &mut rng, &mut std::io::sink()).unwrap()
+        });
+        let mut session = GameSession::new(white, Color::White);
+        session.poll_interval = Duration::from_millis(1);
+        let mut turns = 0;
+        session.turn_policy = Box::new(move || {
+            turns += 1;
+            if turns > 2 { TurnPolicy::Resign } else { TurnPolicy::Play { clock_fraction: 0.5 } }
+        });
+        let mut player = ScriptedPlayer::new("e7", &[]);
+        let mut rng = StdRng::seed_from_u64(42);
+        let outcome = host_player(&mut session, &mut player, &mut rng, &mut std::io::sink()).unwrap();
+        assert_eq!(outcome.winner, Some(Color::Black));
+        assert_eq!(outcome.reason, "RESIGN");
+        assert_eq!(session.ply(), 4);
+        assert_eq!(black.join().unwrap().reason, "RESIGN");
+    }
+}
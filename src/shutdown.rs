@@ -0,0 +1,110 @@
+// Winding the client down without abandoning games.
+//
+// The first request (Ctrl-C) stops new games and starts the grace period,
+// in which games go on as usual. After it, the remaining games either
+// play on with a fraction of their clock, or are resigned by the client.
+// Another request skips the rest of the grace period, and one after the
+// deadline is for exiting right away.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::session::TurnPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AfterDeadline {
+    PlayFast,
+    Resign,
+}
+
+impl AfterDeadline {
+    pub fn from_name(s: &str) -> Option<AfterDeadline> {
+        match s {
+            "fast" => Some(AfterDeadline::PlayFast),
+            "resign" => Some(AfterDeadline::Resign),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Running,
+    // no new games
+    LameDuck,
+    PastDeadline,
+}
+
+pub struct Shutdown {
+    pub grace: Duration,
+    pub after_deadline: AfterDeadline,
+    // of the clock players are told about with AfterDeadline::PlayFast
+    pub fast_clock_fraction: f64,
+    // the deadline, once requested
+    requested: Mutex<Option<Instant>>,
+}
+
+impl Shutdown {
+    pub fn new(grace: Duration, after_deadline: AfterDeadline) -> Shutdown {
+        Shutdown {
+            grace,
+            after_deadline,
+            fast_clock_fraction: 0.2,
+            requested: Mutex::new(None),
+        }
+    }
+
+    // Returns the new stage.
+    pub fn request(&self) -> Stage {
+        let now = Instant::now();
+        let mut requested = self.requested.lock().unwrap();
+        *requested = Some(match *requested {
+            None => now + self.grace,
+            Some(_) => now,
+        });
+        drop(requested);
+        self.stage()
+    }
+
+    pub fn stage(&self) -> Stage {
+        match *self.requested.lock().unwrap() {
+            None => Stage::Running,
+            Some(deadline) if Instant::now() < deadline => Stage::LameDuck,
+            Some(_) => Stage::PastDeadline,
+        }
+    }
+
+    pub fn accepting_games(&self) -> bool {
+        self.stage() == Stage::Running
+    }
+
+    // For GameSession::turn_policy.
+    pub fn turn_policy(&self) -> TurnPolicy {
+        match (self.stage(), self.after_deadline) {
+            (Stage::PastDeadline, AfterDeadline::Resign) => TurnPolicy::Resign,
+            (Stage::PastDeadline, AfterDeadline::PlayFast) =>
+                TurnPolicy::Play { clock_fraction: self.fast_clock_fraction },
+            _ => TurnPolicy::Play { clock_fraction: 1.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages() {
+        let s = Shutdown::new(Duration::from_secs(3600), AfterDeadline::Resign);
+        assert!(s.accepting_games());
+        assert_eq!(s.turn_policy(), TurnPolicy::Play { clock_fraction: 1.0 });
+        assert_eq!(s.request(), Stage::LameDuck);
+        assert!(!s.accepting_games());
+        assert_eq!(s.turn_policy(), TurnPolicy::Play { clock_fraction: 1.0 });
+        assert_eq!(s.request(), Stage::PastDeadline, "impatient");
+        assert_eq!(s.turn_policy(), TurnPolicy::Resign);
+
+        let s = Shutdown::new(Duration::from_secs(0), AfterDeadline::PlayFast);
+        assert_eq!(s.request(), Stage::PastDeadline);
+        assert_eq!(s.turn_policy(), TurnPolicy::Play { clock_fraction: s.fast_clock_fraction });
+    }
+}
@@ -0,0 +1,146 @@
+// How many games to play at once.
+//
+// Each game thinks in its own thread, so with more games than the machine
+// can handle they all get less CPU than their time budgets assume. The
+// limit starts at the maximum and moves one game at a time, down when the
+// CPU is saturated and up when there is headroom and the limit is
+// actually reached.
+
+use std::time::{Duration, Instant};
+
+// (busy, total) jiffies over all CPUs from the first line of /proc/stat.
+pub fn parse_proc_stat(s: &str) -> Option<(u64, u64)> {
+    let line = s.lines().next()?;
+    let mut fields = line.split_whitespace();
+    if fields.next()? != "cpu" {
+        return None;
+    }
+    let values: Vec<u64> = fields.map(|f| f.parse().ok()).collect::<Option<_>>()?;
+    if values.len() < 4 {
+        return None;
+    }
+    // user nice system idle iowait irq softirq steal ...
+    let idle = values[3] + values.get(4).cloned().unwrap_or(0);
+    let total: u64 = values.iter().take(8).sum();
+    Some((total - idle, total))
+}
+
+#[derive(Default)]
+pub struct CpuMonitor {
+    prev: Option<(u64, u64)>,
+}
+
+impl CpuMonitor {
+    // Fraction of the CPU time that was busy since the previous call,
+    // None on the first call or where /proc/stat isn't available.
+    pub fn sample(&mut self) -> Option<f64> {
+        let cur = parse_proc_stat(&std::fs::read_to_string("/proc/stat").ok()?)?;
+        let prev = self.prev.replace(cur)?;
+        let total = cur.1.checked_sub(prev.1)?;
+        if total == 0 {
+            return None;
+        }
+        Some(cur.0.saturating_sub(prev.0) as f64 / total as f64)
+    }
+}
+
+pub struct Throttle {
+    pub max_games: usize,
+    // utilization thresholds
+    pub low: f64,
+    pub high: f64,
+    // between changes of the limit, for the load to settle
+    pub adjust_interval: Duration,
+    limit: usize,
+    load: Option<f64>,
+    last_adjust: Instant,
+}
+
+impl Throttle {
+    pub fn new(max_games: usize) -> Throttle {
+        assert!(max_games >= 1);
+        Throttle {
+            max_games,
+            low: 0.6,
+            high: 0.9,
+            adjust_interval: Duration::from_secs(60),
+            limit: max_games,
+            load: None,
+            last_adjust: Instant::now(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // smoothed utilization
+    pub fn load(&self) -> Option<f64> {
+        self.load
+    }
+
+    // Takes a utilization sample and the number of games in progress.
+    // Returns true if the limit changed.
+    pub fn update(&mut self, sample: f64, games: usize) -> bool {
+        let load = match self.load {
+            Some(load) => 0.7 * load + 0.3 * sample,
+            None => sample,
+        };
+        self.load = Some(load);
+        if self.last_adjust.elapsed() < self.adjust_interval {
+            return false;
+        }
+        let new_limit = if load > self.high {
+            self.limit.saturating_sub(1).max(1)
+        } else if load < self.low && games >= self.limit {
+            (self.limit + 1).min(self.max_games)
+        } else {
+            self.limit
+        };
+        if new_limit == self.limit {
+            return false;
+        }
+        self.limit = new_limit;
+        self.last_adjust = Instant::now();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let s = "cpu  100 5 50 800 40 3 2 0 0 0\ncpu0 100 5 50 800 40 3 2 0 0 0\n";
+        assert_eq!(parse_proc_stat(s), Some((160, 1000)));
+        assert_eq!(parse_proc_stat("intr 1 2 3"), None);
+        assert_eq!(parse_proc_stat(""), None);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let mut t = Throttle::new(3);
+        t.adjust_interval = Duration::from_secs(0);
+        assert_eq!(t.limit(), 3);
+        // saturated
+        for _ in 0..10 {
+            t.update(1.0, 3);
+        }
+        assert_eq!(t.limit(), 1);
+        // idle, but the limit isn't reached, so there is nothing to learn
+        for _ in 0..10 {
+            t.update(0.1, 0);
+        }
+        assert_eq!(t.limit(), 1);
+        assert!(t.update(0.1, 1));
+        assert_eq!(t.limit(), 2);
+        for _ in 0..10 {
+            t.update(0.1, 3);
+        }
+        assert_eq!(t.limit(), 3);
+
+        let mut t = Throttle::new(3);
+        assert!(!t.update(1.0, 3), "too soon");
+    }
+}
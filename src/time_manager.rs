@@ -0,0 +1,192 @@
+// Splits the game clock into budgets for individual decisions.
+//
+// Each decision gets a soft deadline (planned time, checked before
+// starting more work) and a hard one (stop whatever is running).
+// The manager also keeps track of the time actually spent, and how much
+// more the clock lost than that (network, server), so that budgets
+// leave room for it.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Sense,
+    Move,
+}
+
+#[derive(Debug)]
+pub struct Deadline {
+    pub phase: Phase,
+    // seconds since start
+    pub soft: f64,
+    pub hard: f64,
+    start: Instant,
+}
+
+impl Deadline {
+    pub fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    pub fn past_soft(&self) -> bool {
+        self.elapsed() >= self.soft
+    }
+
+    // For eval::Ctx::stop, set once the hard deadline passes.
+    // A fresh flag each time, so the timer can't stop a later decision.
+    pub fn hard_stop(&self) -> Arc<AtomicBool> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let left = (self.hard - self.elapsed()).max(0.0);
+        std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                std::thread::sleep(Duration::from_secs_f64(left));
+                stop.store(true, Ordering::SeqCst);
+            }
+        });
+        stop
+    }
+
+    // Whether one more step of iterative deepening still fits in the
+    // soft budget, if it takes `growth` times as long as everything so far.
+    pub fn can_deepen(&self, growth: f64) -> bool {
+        self.elapsed() * growth < self.soft
+    }
+}
+
+pub struct TimeManager {
+    // added to the clock after each of our moves
+    pub increment: f64,
+    // never planned to be spent
+    pub reserve: f64,
+    // our moves a game is expected to take, and at least how many are still ahead
+    pub expected_moves: f64,
+    pub min_moves_to_go: f64,
+    used: [f64; 2],
+    num_decisions: [usize; 2],
+    // per turn, lost by the clock on top of what we measured
+    overhead: f64,
+    turn_clock: Option<f64>,
+    turn_used: f64,
+}
+
+impl TimeManager {
+    pub fn new(increment: f64) -> TimeManager {
+        TimeManager {
+            increment,
+            reserve: 10.0,
+            expected_moves: 60.0,
+            min_moves_to_go: 30.0,
+            used: [0.0; 2],
+            num_decisions: [0; 2],
+            overhead: 0.0,
+            turn_clock: None,
+            turn_used: 0.0,
+        }
+    }
+
+    // remaining: seconds on our clock, ply: halfmoves played so far,
+    // infoset_size: number of states we could be in.
+    pub fn start(&mut self, phase: Phase, remaining: f64, ply: usize, infoset_size: usize) -> Deadline {
+        if phase == Phase::Sense {
+            self.new_turn(remaining);
+        }
+        let moves_to_go = (self.expected_moves - (ply / 2) as f64).max(self.min_moves_to_go);
+        let available = (remaining - self.reserve).max(0.0);
+        let per_turn = (available / moves_to_go + self.increment - self.overhead).max(0.0);
+        // Few candidate states leave little to think about.
+        let difficulty = ((infoset_size.max(1) as f64).log10() / 2.0).clamp(0.25, 1.5);
+        let soft = (0.5 * per_turn * difficulty).min(0.25 * available);
+        let hard = (3.0 * soft).min(0.5 * available).max(soft);
+        Deadline { phase, soft, hard, start: Instant::now() }
+    }
+
+    pub fn finish(&mut self, deadline: &Deadline) {
+        let t = deadline.elapsed();
+        let i = deadline.phase as usize;
+        self.used[i] += t;
+        self.num_decisions[i] += 1;
+        self.turn_used += t;
+    }
+
+    fn new_turn(&mut self, remaining: f64) {
+        if let Some(prev) = self.turn_clock {
+            let lost = prev + self.increment - remaining;
+            let overhead = (lost - self.turn_used).max(0.0);
+            self.overhead = 0.8 * self.overhead + 0.2 * overhead;
+        }
+        self.turn_clock = Some(remaining);
+        self.turn_used = 0.0;
+    }
+
+    // total seconds spent in sense and move decisions
+    pub fn used(&self, phase: Phase) -> f64 {
+        self.used[phase as usize]
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "time used: sense {:.1}s ({} decisions), move {:.1}s ({} decisions), overhead {:.2}s per turn",
+            self.used[0], self.num_decisions[0],
+            self.used[1], self.num_decisions[1],
+            self.overhead)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budgets() {
+        let mut tm = TimeManager::new(0.0);
+        let d = tm.start(Phase::Sense, 900.0, 0, 100);
+        assert!(d.soft > 1.0 && d.soft < 30.0, "{:?}", d);
+        assert!(d.hard >= d.soft);
+        tm.finish(&d);
+
+        // less time, less budget
+        let d2 = tm.start(Phase::Move, 300.0, 0, 100);
+        assert!(d2.soft < d.soft);
+        // nothing to think about
+        let d3 = tm.start(Phase::Move, 900.0, 0, 1);
+        assert!(d3.soft < d.soft);
+        // the reserve is never planned
+        for &remaining in &[30.0, 10.0, 5.0, 0.0, -1.0] {
+            let d = tm.start(Phase::Move, remaining, 100, 1000);
+            assert!(d.hard <= (remaining - tm.reserve).max(0.0) * 0.5, "{} {:?}", remaining, d);
+        }
+    }
+
+    #[test]
+    fn test_hard_stop() {
+        let mut tm = TimeManager::new(0.0);
+        // almost out of time
+        let d = tm.start(Phase::Move, tm.reserve + 0.4, 40, 1000);
+        assert!(d.hard > 0.0 && d.hard <= 0.2, "{:?}", d);
+        let mut ctx = crate::eval::Ctx::new(crate::game::BoardState::initial());
+        ctx.stop = d.hard_stop();
+        // would take forever
+        crate::eval::search(30, -10000, 10000, &mut ctx);
+        assert!(ctx.aborted());
+        assert!(d.elapsed() < d.hard + 1.0, "{:?} {}", d, d.elapsed());
+    }
+
+    #[test]
+    fn test_overhead() {
+        let mut tm = TimeManager::new(5.0);
+        let mut clock = 900.0;
+        for ply in 0..20 {
+            let d = tm.start(Phase::Sense, clock, 2 * ply, 100);
+            tm.finish(&d);
+            // we barely used any time, but the clock lost two seconds
+            clock += 5.0 - 2.0;
+        }
+        assert!((tm.overhead - 2.0).abs() < 0.1, "{}", tm.overhead);
+        let lazy = TimeManager::new(5.0).start(Phase::Sense, clock, 40, 100);
+        let d = tm.start(Phase::Sense, clock, 40, 100);
+        assert!(d.soft < lazy.soft);
+    }
+}